@@ -0,0 +1,444 @@
+use conv::prelude::*;
+
+use crate::ising::BOLTZMANN;
+
+const RELAXATION_FIT_FLOOR: f64 = 0.1;
+const MEAN_FIELD_TOLERANCE: f64 = 1e-12;
+const MEAN_FIELD_MAX_ITERATIONS: usize = 100_000;
+const WHAM_BINS: usize = 50;
+const WHAM_TOLERANCE: f64 = 1e-10;
+const WHAM_MAX_ITERATIONS: usize = 10_000;
+
+pub fn autocorrelation_function(series: &[f64], max_lag: usize) -> Vec<f64> {
+    let n = series.len();
+    let mean = series.iter().sum::<f64>() / n.value_as::<f64>().unwrap();
+    let covariance = |lag: usize| -> f64 {
+        series
+            .iter()
+            .zip(&series[lag..])
+            .map(|(a, b)| (a - mean) * (b - mean))
+            .sum::<f64>()
+            / (n - lag).value_as::<f64>().unwrap()
+    };
+    let variance = covariance(0);
+    (0..=max_lag.min(n.saturating_sub(1)))
+        .map(|lag| covariance(lag) / variance)
+        .collect()
+}
+
+pub fn energy_relaxation_time(series: &[f64]) -> f64 {
+    let longest = series.len() / 2;
+    let mut max_lag = 16.min(longest);
+    let mut correlations = autocorrelation_function(series, max_lag);
+    while max_lag < longest && correlations.iter().all(|&c| c > RELAXATION_FIT_FLOOR) {
+        max_lag = (2 * max_lag).min(longest);
+        correlations = autocorrelation_function(series, max_lag);
+    }
+    let decay: Vec<(f64, f64)> = correlations
+        .into_iter()
+        .take_while(|&c| c > RELAXATION_FIT_FLOOR)
+        .enumerate()
+        .map(|(lag, c)| (lag.value_as::<f64>().unwrap(), c.ln()))
+        .collect();
+    if decay.len() < 2 {
+        return 0.0;
+    }
+    -1.0 / least_squares_slope(&decay)
+}
+
+pub fn binder_cumulant(magnetizations: &[f64]) -> f64 {
+    let samples = magnetizations.len().value_as::<f64>().unwrap();
+    let second = magnetizations.iter().map(|m| m.powi(2)).sum::<f64>() / samples;
+    let fourth = magnetizations.iter().map(|m| m.powi(4)).sum::<f64>() / samples;
+    1.0 - fourth / (3.0 * second.powi(2))
+}
+
+fn interpolate(curve: &[(f64, f64)], x: f64) -> Option<f64> {
+    curve.windows(2).find_map(|pair| {
+        let ((x0, y0), (x1, y1)) = (pair[0], pair[1]);
+        if (x0..=x1).contains(&x) || (x1..=x0).contains(&x) {
+            if x1 == x0 {
+                Some(y0)
+            } else {
+                Some(y0 + (y1 - y0) * (x - x0) / (x1 - x0))
+            }
+        } else {
+            None
+        }
+    })
+}
+
+fn crossing(a: &[(f64, f64)], b: &[(f64, f64)]) -> Option<f64> {
+    let differences: Vec<(f64, f64)> = a
+        .iter()
+        .filter_map(|&(t, u)| interpolate(b, t).map(|other| (t, u - other)))
+        .collect();
+    differences.windows(2).find_map(|pair| {
+        let ((t0, d0), (t1, d1)) = (pair[0], pair[1]);
+        if d0 == 0.0 {
+            Some(t0)
+        } else if d0 * d1 <= 0.0 {
+            Some(t0 - d0 * (t1 - t0) / (d1 - d0))
+        } else {
+            None
+        }
+    })
+}
+
+pub fn mean_field_magnetization(
+    coupling: f64,
+    field: f64,
+    temperature: f64,
+    coordination: usize,
+) -> f64 {
+    let beta = 1.0 / (BOLTZMANN * temperature);
+    let z = coordination.value_as::<f64>().unwrap();
+    let mut m = if field < 0.0 { -1.0 } else { 1.0 };
+    for _ in 0..MEAN_FIELD_MAX_ITERATIONS {
+        let next = (beta * (field + coupling * z * m)).tanh();
+        if (next - m).abs() < MEAN_FIELD_TOLERANCE {
+            return next;
+        }
+        m = next;
+    }
+    m
+}
+
+pub fn onsager_magnetization(coupling: f64, temperature: f64) -> f64 {
+    let beta = 1.0 / (BOLTZMANN * temperature);
+    let s = (2.0 * beta * coupling).sinh();
+    if s <= 1.0 {
+        return 0.0;
+    }
+    (1.0 - s.powi(-4)).powf(0.125)
+}
+
+pub fn reweighted_average(samples: &[(f64, f64)]) -> f64 {
+    let total_weight: f64 = samples.iter().map(|(_, w)| w).sum();
+    samples.iter().map(|(o, w)| o * w).sum::<f64>() / total_weight
+}
+
+// Windows are (center, k, samples) with harmonic bias 0.5 * k * (M - center)^2, as sampled by
+// `Ising::umbrella_run` at `temperature`; the spring constant and the returned free energy are
+// both in energy units.
+pub fn wham(windows: &[(f64, f64, Vec<f64>)], temperature: f64) -> Vec<(f64, f64)> {
+    let kt = BOLTZMANN * temperature;
+    let all_samples = windows
+        .iter()
+        .flat_map(|(_, _, samples)| samples.iter().copied());
+    let (lo, hi) = all_samples.fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), m| {
+        (lo.min(m), hi.max(m))
+    });
+    if !lo.is_finite() {
+        return Vec::new();
+    }
+    let width = ((hi - lo) / WHAM_BINS.value_as::<f64>().unwrap()).max(f64::EPSILON);
+    let bin_of = |m: f64| {
+        ((m - lo) / width)
+            .floor()
+            .approx_as::<usize>()
+            .unwrap_or(0)
+            .min(WHAM_BINS - 1)
+    };
+    let centers: Vec<f64> = (0..WHAM_BINS)
+        .map(|b| lo + (b.value_as::<f64>().unwrap() + 0.5) * width)
+        .collect();
+    let mut counts = vec![0.0; WHAM_BINS];
+    for (_, _, samples) in windows {
+        for &m in samples {
+            counts[bin_of(m)] += 1.0;
+        }
+    }
+    let bias: Vec<Vec<f64>> = windows
+        .iter()
+        .map(|(center, k, _)| {
+            centers
+                .iter()
+                .map(|x| 0.5 * k * (x - center).powi(2) / kt)
+                .collect()
+        })
+        .collect();
+    let sizes: Vec<f64> = windows
+        .iter()
+        .map(|(_, _, samples)| samples.len().value_as::<f64>().unwrap())
+        .collect();
+    let mut offsets = vec![0.0; windows.len()];
+    let mut probability = vec![0.0; WHAM_BINS];
+    for _ in 0..WHAM_MAX_ITERATIONS {
+        for (b, p) in probability.iter_mut().enumerate() {
+            let denominator: f64 = sizes
+                .iter()
+                .zip(&offsets)
+                .zip(&bias)
+                .map(|((n, f), u)| n * (f - u[b]).exp())
+                .sum();
+            *p = counts[b] / denominator;
+        }
+        let updated: Vec<f64> = bias
+            .iter()
+            .map(|u| {
+                let z: f64 = probability.iter().zip(u).map(|(p, u)| p * (-u).exp()).sum();
+                -z.ln()
+            })
+            .collect();
+        let change = updated
+            .iter()
+            .zip(&offsets)
+            .map(|(a, b)| (a - updated[0] - (b - offsets[0])).abs())
+            .fold(0.0, f64::max);
+        offsets = updated;
+        if change < WHAM_TOLERANCE {
+            break;
+        }
+    }
+    let profile: Vec<(f64, f64)> = centers
+        .into_iter()
+        .zip(&probability)
+        .filter(|(_, &p)| p > 0.0)
+        .map(|(x, p)| (x, -kt * p.ln()))
+        .collect();
+    let minimum = profile
+        .iter()
+        .map(|(_, f)| *f)
+        .fold(f64::INFINITY, f64::min);
+    profile.into_iter().map(|(x, f)| (x, f - minimum)).collect()
+}
+
+pub fn fit_power_law(sizes: &[usize], values: &[f64]) -> f64 {
+    assert!(
+        sizes.len() == values.len() && sizes.len() >= 2,
+        "need at least two matching sizes and values"
+    );
+    let points: Vec<(f64, f64)> = sizes
+        .iter()
+        .zip(values)
+        .map(|(&size, value)| (size.value_as::<f64>().unwrap().ln(), value.ln()))
+        .collect();
+    least_squares_slope(&points)
+}
+
+pub(crate) fn least_squares_slope(points: &[(f64, f64)]) -> f64 {
+    let n = points.len().value_as::<f64>().unwrap();
+    let mean_x = points.iter().map(|(x, _)| x).sum::<f64>() / n;
+    let mean_y = points.iter().map(|(_, y)| y).sum::<f64>() / n;
+    let covariance: f64 = points
+        .iter()
+        .map(|(x, y)| (x - mean_x) * (y - mean_y))
+        .sum();
+    let variance: f64 = points.iter().map(|(x, _)| (x - mean_x).powi(2)).sum();
+    covariance / variance
+}
+
+// Averages the crossings of consecutive system sizes; `None` when no pair of curves crosses.
+pub fn estimate_tc(curves: &[(usize, Vec<(f64, f64)>)]) -> Option<f64> {
+    let mut sorted: Vec<&(usize, Vec<(f64, f64)>)> = curves.iter().collect();
+    sorted.sort_by_key(|(size, _)| *size);
+    let crossings: Vec<f64> = sorted
+        .windows(2)
+        .filter_map(|pair| crossing(&pair[0].1, &pair[1].1))
+        .collect();
+    if crossings.is_empty() {
+        return None;
+    }
+    Some(crossings.iter().sum::<f64>() / crossings.len().value_as::<f64>().unwrap())
+}
+
+pub fn specific_heat_from_energy_curve(curve: &[(f64, f64)]) -> Vec<(f64, f64)> {
+    if curve.len() < 2 {
+        return Vec::new();
+    }
+    let last = curve.len() - 1;
+    (0..curve.len())
+        .map(|i| {
+            let (lo, hi) = (i.saturating_sub(1), (i + 1).min(last));
+            let derivative = (curve[hi].1 - curve[lo].1) / (curve[hi].0 - curve[lo].0);
+            (curve[i].0, derivative)
+        })
+        .collect()
+}
+
+pub struct DensityOfStates {
+    pub energies: Vec<f64>,
+    pub ln_g: Vec<f64>,
+}
+
+impl DensityOfStates {
+    pub fn new(entries: Vec<(f64, f64)>) -> Self {
+        let (energies, ln_g) = entries.into_iter().unzip();
+        DensityOfStates { energies, ln_g }
+    }
+
+    pub fn specific_heat(&self, betas: &[f64]) -> Vec<(f64, f64)> {
+        betas
+            .iter()
+            .map(|&beta| {
+                let exponents: Vec<f64> = self
+                    .energies
+                    .iter()
+                    .zip(&self.ln_g)
+                    .map(|(e, ln_g)| ln_g - beta * e)
+                    .collect();
+                let shift = exponents.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+                let weights: Vec<f64> = exponents.iter().map(|x| (x - shift).exp()).collect();
+                let partition: f64 = weights.iter().sum();
+                let moment = |power: i32| -> f64 {
+                    self.energies
+                        .iter()
+                        .zip(&weights)
+                        .map(|(e, w)| e.powi(power) * w)
+                        .sum::<f64>()
+                        / partition
+                };
+                let variance = moment(2) - moment(1).powi(2);
+                let temperature = 1.0 / (BOLTZMANN * beta);
+                (temperature, BOLTZMANN * beta.powi(2) * variance)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, SeedableRng};
+    use rand_chacha::ChaCha12Rng;
+
+    use super::*;
+
+    #[test]
+    fn autocorrelation_of_white_noise_drops_after_lag_zero() {
+        let mut rng = ChaCha12Rng::seed_from_u64(1);
+        let series: Vec<f64> = (0..20_000).map(|_| rng.gen::<f64>()).collect();
+        let correlations = autocorrelation_function(&series, 10);
+        assert_eq!(correlations.len(), 11);
+        assert!((correlations[0] - 1.0).abs() < 1e-12);
+        assert!(correlations[1..].iter().all(|c| c.abs() < 0.03));
+    }
+
+    #[test]
+    fn estimate_tc_finds_a_synthetic_crossing() {
+        let curve = |slope: f64| -> Vec<(f64, f64)> {
+            (0..=10)
+                .map(|n| {
+                    let t = 2.0 + 0.05 * f64::from(n);
+                    (t, 0.5 - slope * (t - 2.27))
+                })
+                .collect()
+        };
+        let curves = vec![(16, curve(2.0)), (8, curve(1.0)), (32, curve(4.0))];
+        let tc = estimate_tc(&curves).unwrap();
+        assert!((tc - 2.27).abs() < 1e-9);
+
+        let parallel = vec![
+            (8, curve(1.0)),
+            (
+                16,
+                curve(1.0).into_iter().map(|(t, u)| (t, u + 0.1)).collect(),
+            ),
+        ];
+        assert_eq!(estimate_tc(&parallel), None);
+    }
+
+    #[test]
+    fn specific_heat_differentiates_a_quadratic_curve() {
+        let curve: Vec<(f64, f64)> = (0..6)
+            .map(|n| {
+                let t = 1.0 + 0.5 * f64::from(n);
+                (t, t * t)
+            })
+            .collect();
+        let heat = specific_heat_from_energy_curve(&curve);
+        assert_eq!(heat.len(), 6);
+        for &(t, c) in &heat[1..5] {
+            assert!((c - 2.0 * t).abs() < 1e-12);
+        }
+        assert!((heat[0].1 - 2.5).abs() < 1e-12);
+        assert!((heat[5].1 - 6.5).abs() < 1e-12);
+        assert!(specific_heat_from_energy_curve(&curve[..1]).is_empty());
+    }
+
+    #[test]
+    fn wham_recovers_a_harmonic_profile_from_gaussian_windows() {
+        let (kt, stiffness, k) = (2.0, 4.0, 40.0);
+        let mut rng = ChaCha12Rng::seed_from_u64(2);
+        let mut gaussian = |mean: f64, variance: f64| {
+            let (u, v): (f64, f64) = (rng.gen(), rng.gen());
+            mean + variance.sqrt()
+                * (-2.0 * (1.0 - u).ln()).sqrt()
+                * (std::f64::consts::TAU * v).cos()
+        };
+        let windows: Vec<(f64, f64, Vec<f64>)> = (-4..=4)
+            .map(|n| {
+                let center = 0.25 * f64::from(n);
+                let mean = k * center / (stiffness + k);
+                let variance = kt / (stiffness + k);
+                let samples = (0..5000).map(|_| gaussian(mean, variance)).collect();
+                (center, k, samples)
+            })
+            .collect();
+        let profile = wham(&windows, kt / BOLTZMANN);
+        let reference = profile
+            .iter()
+            .min_by(|a, b| a.0.abs().partial_cmp(&b.0.abs()).unwrap())
+            .copied()
+            .unwrap();
+        for &(m, f) in profile.iter().filter(|(m, _)| m.abs() < 0.8) {
+            let exact = 0.5 * stiffness * (m * m - reference.0 * reference.0);
+            assert!(
+                (f - reference.1 - exact).abs() < 0.1,
+                "{} {} {}",
+                m,
+                f,
+                exact
+            );
+        }
+    }
+
+    #[test]
+    fn mean_field_orders_only_below_z_times_coupling() {
+        let hot = mean_field_magnetization(1.0, 0.0, 8.0 / BOLTZMANN, 4);
+        assert!(hot.abs() < 1e-9);
+        let cold = mean_field_magnetization(1.0, 0.0, 1.0 / BOLTZMANN, 4);
+        assert!((cold - (4.0 * cold).tanh()).abs() < 1e-9);
+        assert!(cold > 0.99);
+        let reversed = mean_field_magnetization(1.0, -0.1, 1.0 / BOLTZMANN, 4);
+        assert!((reversed + cold).abs() < 1e-3);
+    }
+
+    #[test]
+    fn relaxation_time_of_an_exponentially_correlated_series() {
+        let mut rng = ChaCha12Rng::seed_from_u64(4);
+        let tau = 5.0;
+        let decay = (-1.0f64 / tau).exp();
+        let mut value = 0.0;
+        let series: Vec<f64> = (0..200_000)
+            .map(|_| {
+                value = decay * value + rng.gen::<f64>() - 0.5;
+                value
+            })
+            .collect();
+        assert!((energy_relaxation_time(&series) - tau).abs() < 0.5);
+    }
+
+    #[test]
+    fn onsager_magnetization_matches_known_values() {
+        let tc = 2.0 / (1.0 + 2.0f64.sqrt()).ln();
+        let m = |kt: f64| onsager_magnetization(1.0, kt / BOLTZMANN);
+        assert!((m(1.0) - 0.99928).abs() < 1e-4);
+        assert!((m(2.0) - 0.91132).abs() < 1e-4);
+        assert!(m(0.99 * tc) > 0.0);
+        assert_eq!(m(1.01 * tc), 0.0);
+        assert_eq!(m(5.0), 0.0);
+    }
+
+    #[test]
+    fn fit_power_law_recovers_a_known_exponent() {
+        let sizes = [4, 8, 16, 32, 64];
+        let values: Vec<f64> = sizes
+            .iter()
+            .map(|&size| 3.0 * size.value_as::<f64>().unwrap().powf(1.75))
+            .collect();
+        assert!((fit_power_law(&sizes, &values) - 1.75).abs() < 1e-12);
+        let decaying: Vec<f64> = values.iter().map(|v| v.recip()).collect();
+        assert!((fit_power_law(&sizes, &decaying) + 1.75).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,156 @@
+use std::collections::HashMap;
+
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha12Rng;
+
+use crate::error::JikiError;
+use crate::ising::{check_temperature, BoundaryCondition, Lattice, Metric, BOLTZMANN};
+use crate::topology::LatticePoint;
+
+pub trait ContinuousSpin: Copy {
+    type Field: Copy;
+
+    const STEP_SIZE: f64;
+
+    fn aligned() -> Self;
+
+    fn random(rng: &mut impl Rng) -> Self;
+
+    // Metropolis accepts with the bare Boltzmann ratio, so the proposal must be symmetric:
+    // moving from `a` to `b` is exactly as likely as moving from `b` to `a`.
+    fn perturbed(&self, step_size: f64, rng: &mut impl Rng) -> Self;
+
+    fn exchange(&self, other: &Self) -> f64;
+
+    fn field_energy(&self, field: &Self::Field) -> f64;
+}
+
+#[derive(Clone)]
+pub struct ContinuousModel<S: ContinuousSpin> {
+    pub lattice: Lattice,
+    pub spins: HashMap<LatticePoint, S>,
+    pub coupling: f64,
+    pub applied_field: S::Field,
+    pub(crate) temperature: f64,
+    pub boundary: Vec<BoundaryCondition>,
+    pub step_size: f64,
+    pub(crate) rng: ChaCha12Rng,
+}
+
+impl<S: ContinuousSpin> ContinuousModel<S> {
+    pub fn new(lattice: Lattice, coupling: f64, applied_field: S::Field, temperature: f64) -> Self {
+        ContinuousModel::try_new(lattice, coupling, applied_field, temperature)
+            .unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    pub fn try_new(
+        lattice: Lattice,
+        coupling: f64,
+        applied_field: S::Field,
+        temperature: f64,
+    ) -> Result<Self, JikiError> {
+        check_temperature(temperature)?;
+        let spins = lattice
+            .all_points()
+            .map(|idx| (idx, S::aligned()))
+            .collect();
+        let boundary = vec![BoundaryCondition::Open; lattice.dimension];
+        Ok(ContinuousModel {
+            lattice,
+            spins,
+            coupling,
+            applied_field,
+            temperature,
+            boundary,
+            step_size: S::STEP_SIZE,
+            rng: ChaCha12Rng::from_entropy(),
+        })
+    }
+
+    pub fn with_seed(
+        lattice: Lattice,
+        coupling: f64,
+        applied_field: S::Field,
+        temperature: f64,
+        seed: u64,
+    ) -> Self {
+        let mut model = ContinuousModel::new(lattice, coupling, applied_field, temperature);
+        model.rng = ChaCha12Rng::seed_from_u64(seed);
+        model
+    }
+
+    pub fn temperature(&self) -> f64 {
+        self.temperature
+    }
+
+    pub fn set_temperature(&mut self, temperature: f64) -> Result<(), JikiError> {
+        check_temperature(temperature)?;
+        self.temperature = temperature;
+        Ok(())
+    }
+
+    pub fn set_boundary(&mut self, boundary: Vec<BoundaryCondition>) {
+        assert!(
+            boundary.len() == self.lattice.dimension,
+            "boundary vector does not match dimension of lattice"
+        );
+        self.boundary = boundary;
+    }
+
+    pub fn randomize(&mut self) {
+        for idx in self.lattice.all_points() {
+            let spin = S::random(&mut self.rng);
+            self.spins.insert(idx, spin);
+        }
+    }
+
+    pub fn local_energy(&self, idx: &[usize]) -> Result<f64, JikiError> {
+        let spin = self.spins.get(idx).ok_or(JikiError::InvalidIndex)?;
+        let exchange: f64 = self
+            .lattice
+            .neighbors(idx, &self.boundary, Metric::Manhattan)
+            .iter()
+            .map(|nidx| spin.exchange(self.spins.get(nidx).unwrap()))
+            .sum();
+        Ok(-self.coupling * exchange + spin.field_energy(&self.applied_field))
+    }
+
+    pub fn total_energy(&self) -> f64 {
+        let field_energy: f64 = self
+            .spins
+            .values()
+            .map(|spin| spin.field_energy(&self.applied_field))
+            .sum();
+        let local: f64 = self
+            .spins
+            .keys()
+            .map(|idx| self.local_energy(idx).unwrap())
+            .sum();
+        0.5 * (local + field_energy)
+    }
+
+    pub fn metropolis_stepper(&mut self) {
+        let idx: LatticePoint = self
+            .lattice
+            .size
+            .iter()
+            .map(|&cap| self.rng.gen_range(0..cap))
+            .collect();
+        let spin = *self.spins.get(&idx).unwrap();
+        let proposal = spin.perturbed(self.step_size, &mut self.rng);
+        let init_energy = self.local_energy(&idx).unwrap();
+        self.spins.insert(idx.clone(), proposal);
+        let energy_change = self.local_energy(&idx).unwrap() - init_energy;
+        if energy_change > 0.0
+            && self.rng.gen::<f64>() > (-energy_change / (BOLTZMANN * self.temperature)).exp()
+        {
+            self.spins.insert(idx, spin);
+        }
+    }
+
+    pub fn sweep(&mut self) {
+        for _ in 0..self.spins.len() {
+            self.metropolis_stepper();
+        }
+    }
+}
@@ -0,0 +1,103 @@
+use rand::{Rng, RngCore};
+
+use crate::ising::*;
+
+// `rng` is the only source of randomness an update rule may use. `Ising::step_with` and
+// `Ising::run` pass the model's own seeded stream here, so seeded runs stay reproducible.
+pub trait Updater {
+    fn step(&self, ising: &mut Ising, rng: &mut dyn RngCore);
+}
+
+pub struct Metropolis;
+
+pub struct Glauber;
+
+pub struct Kawasaki;
+
+fn random_site(ising: &Ising, rng: &mut dyn RngCore) -> Vec<usize> {
+    let mut idx = Vec::new();
+    for d in 0..ising.lattice.dimension {
+        idx.push(rng.gen_range(0..ising.lattice.size[d]))
+    }
+    idx
+}
+
+pub fn metropolis_site(ising: &mut Ising, idx: &[usize], rng: &mut dyn RngCore) {
+    let energy_change = ising.flip_energy_delta(idx).unwrap() + ising.bias_change(&[idx.to_vec()]);
+    if energy_change > 0.0
+        && rng.gen::<f64>() > (-energy_change / (BOLTZMANN * ising.temperature)).exp()
+    {
+        return;
+    }
+    let spin = ising.get_spin(idx).unwrap();
+    ising.set_spin(idx, spin.flipped()).unwrap();
+    ising.accepted_flips += 1;
+}
+
+impl Updater for Metropolis {
+    fn step(&self, ising: &mut Ising, rng: &mut dyn RngCore) {
+        let idx = random_site(ising, rng);
+        if ising.pinned.contains(&idx) || !ising.spins.contains_key(&idx) {
+            return;
+        }
+        metropolis_site(ising, &idx, rng);
+    }
+}
+
+impl Updater for Glauber {
+    fn step(&self, ising: &mut Ising, rng: &mut dyn RngCore) {
+        let idx = random_site(ising, rng);
+        if ising.pinned.contains(&idx) || !ising.spins.contains_key(&idx) {
+            return;
+        }
+        let energy_change = ising.flip_energy_delta(idx.as_slice()).unwrap()
+            + ising.bias_change(std::slice::from_ref(&idx));
+        let acceptance = 1.0 / (1.0 + (energy_change / (BOLTZMANN * ising.temperature)).exp());
+        if rng.gen::<f64>() >= acceptance {
+            return;
+        }
+        let spin = ising.get_spin(idx.as_slice()).unwrap();
+        ising.set_spin(idx.as_slice(), spin.flipped()).unwrap();
+        ising.accepted_flips += 1;
+    }
+}
+
+impl Updater for Kawasaki {
+    fn step(&self, ising: &mut Ising, rng: &mut dyn RngCore) {
+        let idx = random_site(ising, rng);
+        if ising.pinned.contains(&idx) || !ising.spins.contains_key(&idx) {
+            return;
+        }
+        let neighbors = ising.nearest_neighbor(idx.as_slice()).unwrap();
+        if neighbors.is_empty() {
+            return;
+        }
+        let other = neighbors[rng.gen_range(0..neighbors.len())].clone();
+        if ising.pinned.contains(&other) {
+            return;
+        }
+        let spin = ising.get_spin(idx.as_slice()).unwrap();
+        let other_spin = ising.get_spin(other.as_slice()).unwrap();
+        if spin == other_spin {
+            return;
+        }
+        // Swapping two opposite spins flips both, so the bias sees the same change either way.
+        let bias_change = ising.bias_change(&[idx.clone(), other.clone()]);
+        let init_energy = ising.local_energy(idx.as_slice()).unwrap()
+            + ising.local_energy(other.as_slice()).unwrap();
+        ising.set_spin(idx.as_slice(), other_spin).unwrap();
+        ising.set_spin(other.as_slice(), spin).unwrap();
+        let energy_change = ising.local_energy(idx.as_slice()).unwrap()
+            + ising.local_energy(other.as_slice()).unwrap()
+            + bias_change
+            - init_energy;
+        if energy_change > 0.0
+            && rng.gen::<f64>() > (-energy_change / (BOLTZMANN * ising.temperature)).exp()
+        {
+            ising.set_spin(idx.as_slice(), spin).unwrap();
+            ising.set_spin(other.as_slice(), other_spin).unwrap();
+        } else {
+            ising.accepted_flips += 2;
+        }
+    }
+}
@@ -0,0 +1,120 @@
+use conv::prelude::*;
+use rand::SeedableRng;
+use rand_chacha::ChaCha12Rng;
+
+use crate::ising::{Ising, BOLTZMANN};
+
+pub struct Ensemble {
+    pub replicas: Vec<Ising>,
+    measurements: Vec<(f64, f64, f64)>,
+}
+
+impl Ensemble {
+    pub fn new(ising: &Ising, n: usize, seed: u64) -> Self {
+        let replicas = (0..n)
+            .map(|k| {
+                let mut replica = ising.clone();
+                replica.rng =
+                    ChaCha12Rng::seed_from_u64(seed.wrapping_add(k.value_as::<u64>().unwrap()));
+                replica.randomize();
+                replica
+            })
+            .collect();
+        Ensemble {
+            replicas,
+            measurements: Vec::new(),
+        }
+    }
+
+    pub fn mean_observables(&mut self, sweeps: usize) -> (f64, f64, f64) {
+        self.mean_observables_with_progress(sweeps, 1, |_, _| {})
+    }
+
+    pub fn mean_observables_with_progress(
+        &mut self,
+        sweeps: usize,
+        report_interval: usize,
+        mut on_progress: impl FnMut(usize, &Ising),
+    ) -> (f64, f64, f64) {
+        assert!(sweeps > 0, "sweeps must be positive");
+        assert!(report_interval > 0, "report interval must be positive");
+        self.measurements = self
+            .replicas
+            .iter_mut()
+            .map(|replica| {
+                let mut energies = Vec::with_capacity(sweeps);
+                let mut magnetizations = Vec::with_capacity(sweeps);
+                for s in 1..=sweeps {
+                    replica.sweep();
+                    if s % report_interval == 0 {
+                        on_progress(s, replica);
+                    }
+                    energies.push(replica.total_energy());
+                    magnetizations.push(replica.magnetization());
+                }
+                let samples = sweeps.value_as::<f64>().unwrap();
+                let energy = energies.iter().sum::<f64>() / samples;
+                let magnetization = magnetizations.iter().sum::<f64>() / samples;
+                let second_moment = magnetizations.iter().map(|m| m * m).sum::<f64>() / samples;
+                let sites = replica.spins.len().value_as::<f64>().unwrap();
+                let beta = 1.0 / (BOLTZMANN * replica.temperature);
+                let susceptibility = sites * beta * (second_moment - magnetization * magnetization);
+                (energy, magnetization, susceptibility)
+            })
+            .collect();
+        self.column_statistics(|(mean, _)| mean)
+    }
+
+    // Standard errors across replicas of the values from the last `mean_observables` call.
+    pub fn standard_errors(&self) -> (f64, f64, f64) {
+        self.column_statistics(|(_, error)| error)
+    }
+
+    fn column_statistics(&self, pick: impl Fn((f64, f64)) -> f64) -> (f64, f64, f64) {
+        let n = self.measurements.len().value_as::<f64>().unwrap();
+        let stats = |column: fn(&(f64, f64, f64)) -> f64| {
+            let mean = self.measurements.iter().map(column).sum::<f64>() / n;
+            let variance = self
+                .measurements
+                .iter()
+                .map(|m| (column(m) - mean).powi(2))
+                .sum::<f64>()
+                / (n - 1.0).max(1.0);
+            pick((mean, (variance / n).sqrt()))
+        };
+        (stats(|m| m.0), stats(|m| m.1), stats(|m| m.2))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ising::{BoundaryCondition, Lattice};
+
+    #[test]
+    fn ensemble_average_agrees_with_a_long_run() {
+        let mut lattice = Lattice::new(2);
+        lattice.set_size(vec![4, 4]);
+        let mut ising = Ising::with_seed(lattice, 1.0, 0.0, 5.0 / BOLTZMANN, 17);
+        ising.set_boundary(vec![BoundaryCondition::Periodic; 2]);
+        let mut ensemble = Ensemble::new(&ising, 8, 3);
+        let (energy, _, _) = ensemble.mean_observables(200);
+        let (energy_error, _, _) = ensemble.standard_errors();
+        assert!(energy_error > 0.0);
+
+        ising.randomize();
+        let long_run = (0..1600)
+            .map(|_| {
+                ising.sweep();
+                ising.total_energy()
+            })
+            .sum::<f64>()
+            / 1600.0;
+        assert!(
+            (energy - long_run).abs() < 0.5,
+            "{} vs {}",
+            energy,
+            long_run
+        );
+    }
+}
@@ -0,0 +1,65 @@
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum JikiError {
+    InvalidIndex,
+    VacantSite,
+    NotASubset,
+    IncompatibleSections,
+    EmptyCover,
+    ShapeMismatch,
+    InvalidMagnetization,
+    NotBipartite,
+    InvalidSpin(String),
+    TooManySites,
+    LatticeTooLarge,
+    InvalidTemperature(f64),
+    InvalidCheckpoint,
+    UnsupportedVersion(u8),
+    Io(String),
+    InvalidConfig(String),
+}
+
+impl fmt::Display for JikiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JikiError::InvalidIndex => write!(f, "Invalid Index"),
+            JikiError::VacantSite => write!(f, "Vacant Site"),
+            JikiError::NotASubset => {
+                write!(
+                    f,
+                    "Target Open Set is not a subset of the provided start set!"
+                )
+            }
+            JikiError::IncompatibleSections => {
+                write!(f, "Sections do not agree on the overlap of the open sets!")
+            }
+            JikiError::EmptyCover => write!(f, "No open sets provided to glue!"),
+            JikiError::ShapeMismatch => write!(f, "Lattice shapes do not match!"),
+            JikiError::NotBipartite => write!(f, "Lattice is not bipartite!"),
+            JikiError::InvalidTemperature(temperature) => {
+                write!(f, "Temperature must be positive, got {}!", temperature)
+            }
+            JikiError::TooManySites => write!(f, "Too many sites to enumerate exhaustively!"),
+            JikiError::LatticeTooLarge => write!(f, "Lattice is too large to checkpoint!"),
+            JikiError::InvalidCheckpoint => write!(f, "Data is not a Jiki checkpoint!"),
+            JikiError::UnsupportedVersion(version) => {
+                write!(f, "Unsupported checkpoint version {}!", version)
+            }
+            JikiError::Io(message) => write!(f, "I/O error: {}", message),
+            JikiError::InvalidConfig(message) => write!(f, "Invalid config: {}", message),
+            JikiError::InvalidSpin(text) => write!(f, "Cannot parse {:?} as a spin!", text),
+            JikiError::InvalidMagnetization => {
+                write!(f, "Magnetization cannot be realized on this lattice!")
+            }
+        }
+    }
+}
+
+impl std::error::Error for JikiError {}
+
+impl From<std::io::Error> for JikiError {
+    fn from(error: std::io::Error) -> Self {
+        JikiError::Io(error.to_string())
+    }
+}
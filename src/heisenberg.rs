@@ -0,0 +1,169 @@
+use conv::prelude::*;
+use rand::Rng;
+
+use crate::continuous::{ContinuousModel, ContinuousSpin};
+use crate::error::JikiError;
+use crate::ising::IsingModel;
+
+fn dot(a: &[f64; 3], b: &[f64; 3]) -> f64 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct HeisenbergSpin([f64; 3]);
+
+impl HeisenbergSpin {
+    pub fn new(components: [f64; 3]) -> Self {
+        let norm = components.iter().map(|c| c * c).sum::<f64>().sqrt();
+        assert!(norm > 0.0, "spin vector must be nonzero");
+        HeisenbergSpin(components.map(|c| c / norm))
+    }
+
+    pub fn up() -> Self {
+        HeisenbergSpin([0.0, 0.0, 1.0])
+    }
+
+    pub fn components(&self) -> [f64; 3] {
+        self.0
+    }
+
+    pub fn dot(&self, other: &HeisenbergSpin) -> f64 {
+        dot(&self.0, &other.0)
+    }
+
+    pub fn rotated(&self, rotation: &[[f64; 3]; 3]) -> Self {
+        HeisenbergSpin::new(rotation.map(|row| dot(&row, &self.0)))
+    }
+}
+
+impl ContinuousSpin for HeisenbergSpin {
+    type Field = [f64; 3];
+
+    const STEP_SIZE: f64 = 0.5;
+
+    fn aligned() -> Self {
+        HeisenbergSpin::up()
+    }
+
+    fn random(rng: &mut impl Rng) -> Self {
+        let z: f64 = rng.gen_range(-1.0..=1.0);
+        let phi: f64 = rng.gen_range(0.0..std::f64::consts::TAU);
+        let r = (1.0 - z * z).sqrt();
+        HeisenbergSpin([r * phi.cos(), r * phi.sin(), z])
+    }
+
+    // Adding an isotropically distributed kick and renormalizing gives a proposal whose
+    // density depends only on the angle between the old and new spin. A kick that exactly
+    // cancels the spin is discarded rather than normalized.
+    fn perturbed(&self, step_size: f64, rng: &mut impl Rng) -> Self {
+        let kick = HeisenbergSpin::random(rng);
+        let proposal: [f64; 3] = [0, 1, 2].map(|i| self.0[i] + step_size * kick.0[i]);
+        if dot(&proposal, &proposal) > 0.0 {
+            HeisenbergSpin::new(proposal)
+        } else {
+            *self
+        }
+    }
+
+    fn exchange(&self, other: &Self) -> f64 {
+        self.dot(other)
+    }
+
+    fn field_energy(&self, field: &[f64; 3]) -> f64 {
+        -dot(field, &self.0)
+    }
+}
+
+pub type Heisenberg = ContinuousModel<HeisenbergSpin>;
+
+impl ContinuousModel<HeisenbergSpin> {
+    pub fn rotate_all(&mut self, rotation: &[[f64; 3]; 3]) {
+        for spin in self.spins.values_mut() {
+            *spin = spin.rotated(rotation);
+        }
+    }
+
+    pub fn magnetization(&self) -> [f64; 3] {
+        let sites = self.spins.len().value_as::<f64>().unwrap();
+        let mut total = [0.0; 3];
+        for spin in self.spins.values() {
+            for (t, c) in total.iter_mut().zip(spin.0) {
+                *t += c;
+            }
+        }
+        total.map(|t| t / sites)
+    }
+}
+
+impl IsingModel for Heisenberg {
+    fn local_energy(&self, idx: &[usize]) -> Result<f64, JikiError> {
+        ContinuousModel::local_energy(self, idx)
+    }
+
+    fn total_energy(&self) -> f64 {
+        ContinuousModel::total_energy(self)
+    }
+
+    fn net_magnetization(&self) -> f64 {
+        let m = self.magnetization();
+        dot(&m, &m).sqrt()
+    }
+
+    fn step(&mut self) {
+        self.metropolis_stepper();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha12Rng;
+
+    use super::*;
+    use crate::ising::{BoundaryCondition, Lattice, BOLTZMANN};
+
+    fn heisenberg(size: &[usize], seed: u64) -> Heisenberg {
+        let mut lattice = Lattice::new(size.len());
+        lattice.set_size(size.to_vec());
+        Heisenberg::with_seed(lattice, 1.0, [0.0; 3], 1.0 / BOLTZMANN, seed)
+    }
+
+    #[test]
+    fn energy_is_invariant_under_global_rotation() {
+        let mut model = heisenberg(&[4, 4], 7);
+        model.set_boundary(vec![BoundaryCondition::Periodic; 2]);
+        model.randomize();
+        let before = model.total_energy();
+        let (c, s) = (0.3f64.cos(), 0.3f64.sin());
+        let rotation = [[c, -s, 0.0], [s, c, 0.0], [0.0, 0.0, 1.0]];
+        let tilt = [[1.0, 0.0, 0.0], [0.0, c, -s], [0.0, s, c]];
+        model.rotate_all(&rotation);
+        model.rotate_all(&tilt);
+        assert!((model.total_energy() - before).abs() < 1e-9);
+    }
+
+    #[test]
+    fn proposal_is_isotropic() {
+        let mut rng = ChaCha12Rng::seed_from_u64(3);
+        let mean_overlap = |spin: HeisenbergSpin, rng: &mut ChaCha12Rng| {
+            (0..20_000)
+                .map(|_| spin.dot(&spin.perturbed(0.5, rng)))
+                .sum::<f64>()
+                / 20_000.0
+        };
+        let axis = mean_overlap(HeisenbergSpin::up(), &mut rng);
+        let diagonal = mean_overlap(HeisenbergSpin::new([1.0, 1.0, 1.0]), &mut rng);
+        assert!((axis - diagonal).abs() < 0.005);
+    }
+
+    #[test]
+    fn large_steps_never_degenerate() {
+        let mut rng = ChaCha12Rng::seed_from_u64(5);
+        let spin = HeisenbergSpin::up();
+        for _ in 0..10_000 {
+            let proposal = spin.perturbed(1.0, &mut rng).components();
+            let norm = dot(&proposal, &proposal).sqrt();
+            assert!((norm - 1.0).abs() < 1e-12);
+        }
+    }
+}
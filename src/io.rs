@@ -0,0 +1,890 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Read, Write};
+use std::path::Path;
+
+#[cfg(feature = "ndarray")]
+use ndarray::{ArrayD, Dimension, IxDyn};
+
+use conv::prelude::*;
+use rand::SeedableRng;
+use rand_chacha::ChaCha12Rng;
+
+use crate::error::JikiError;
+use crate::ising::*;
+use crate::topology::LatticePoint;
+
+const CHECKPOINT_MAGIC: &[u8; 4] = b"JIKI";
+const CHECKPOINT_VERSION: u8 = 3;
+
+fn read_u8<R: Read>(reader: &mut R) -> io::Result<u8> {
+    let mut byte = [0; 1];
+    reader.read_exact(&mut byte)?;
+    Ok(byte[0])
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_f64<R: Read>(reader: &mut R) -> io::Result<f64> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(f64::from_le_bytes(bytes))
+}
+
+fn pack(bits: &[bool]) -> Vec<u8> {
+    let mut packed = vec![0u8; bits.len().div_ceil(8)];
+    for (n, &bit) in bits.iter().enumerate() {
+        if bit {
+            packed[n / 8] |= 1 << (n % 8);
+        }
+    }
+    packed
+}
+
+fn unpack(packed: &[u8], len: usize) -> Vec<bool> {
+    (0..len)
+        .map(|n| packed[n / 8] >> (n % 8) & 1 == 1)
+        .collect()
+}
+
+impl Ising {
+    /// One bit per occupied site, in `points_in_order`; vacancies take no bits.
+    pub fn pack_bits(&self) -> Vec<u8> {
+        pack(&self.configuration_as(true, false))
+    }
+
+    pub fn unpack_bits(&mut self, packed: &[u8]) -> Result<(), JikiError> {
+        let points: Vec<LatticePoint> = self
+            .lattice
+            .points_in_order()
+            .into_iter()
+            .filter(|idx| self.spins.contains_key(idx))
+            .collect();
+        if packed.len() != points.len().div_ceil(8) {
+            return Err(JikiError::ShapeMismatch);
+        }
+        for (idx, up) in points.iter().zip(unpack(packed, points.len())) {
+            let spin = if up { Spin::Up } else { Spin::Down };
+            self.set_spin(idx, spin).unwrap();
+        }
+        Ok(())
+    }
+
+    pub fn rle_encode(&self) -> Vec<(Spin, usize)> {
+        let mut runs: Vec<(Spin, usize)> = Vec::new();
+        for idx in self.sites() {
+            let spin = self.get_spin(&idx).unwrap();
+            match runs.last_mut() {
+                Some((last, length)) if *last == spin => *length += 1,
+                _ => runs.push((spin, 1)),
+            }
+        }
+        runs
+    }
+
+    pub fn rle_decode(&mut self, runs: &[(Spin, usize)]) -> Result<(), JikiError> {
+        let sites: Vec<LatticePoint> = self.sites().collect();
+        if runs.iter().map(|(_, length)| length).sum::<usize>() != sites.len() {
+            return Err(JikiError::ShapeMismatch);
+        }
+        let spins = runs
+            .iter()
+            .flat_map(|&(spin, length)| std::iter::repeat_n(spin, length));
+        for (idx, spin) in sites.iter().zip(spins) {
+            self.set_spin(idx, spin).unwrap();
+        }
+        Ok(())
+    }
+
+    pub fn save_checkpoint<W: Write>(&self, mut writer: W) -> Result<(), JikiError> {
+        let as_u32 = |n: usize| n.value_as::<u32>().map_err(|_| JikiError::LatticeTooLarge);
+        writer.write_all(CHECKPOINT_MAGIC)?;
+        writer.write_all(&[CHECKPOINT_VERSION])?;
+        writer.write_all(&as_u32(self.lattice.dimension)?.to_le_bytes())?;
+        for &size in &self.lattice.size {
+            writer.write_all(&as_u32(size)?.to_le_bytes())?;
+        }
+        writer.write_all(&pack(
+            &self.configuration_with_vacancies(true, false, false),
+        ))?;
+        for value in [self.coupling, self.applied_field, self.temperature] {
+            writer.write_all(&value.to_le_bytes())?;
+        }
+        writer.write_all(&self.rng.get_seed())?;
+        writer.write_all(&self.rng.get_stream().to_le_bytes())?;
+        writer.write_all(&self.rng.get_word_pos().to_le_bytes())?;
+        self.write_checkpoint_settings(&mut writer)
+    }
+
+    // Version 3 settings follow the generator state. The bias is a closure and is not saved;
+    // reinstall it with `set_bias` after loading.
+    fn write_checkpoint_settings<W: Write>(&self, writer: &mut W) -> Result<(), JikiError> {
+        let points = self.lattice.points_in_order();
+        writer.write_all(&pack(
+            &self.configuration_with_vacancies(false, false, true),
+        ))?;
+        let pinned: Vec<bool> = points.iter().map(|idx| self.pinned.contains(idx)).collect();
+        writer.write_all(&pack(&pinned))?;
+        for bc in &self.boundary {
+            let tag = match bc {
+                BoundaryCondition::Open => 0u8,
+                BoundaryCondition::Periodic => 1,
+            };
+            writer.write_all(&[tag])?;
+        }
+        let metric = match self.metric {
+            Metric::Manhattan => 0u8,
+            Metric::Chebyshev => 1,
+            Metric::Euclidean => 2,
+        };
+        writer.write_all(&[metric])?;
+        match self.interaction {
+            Interaction::NearestNeighbor => writer.write_all(&[0])?,
+            Interaction::PowerLaw { sigma, cutoff } => {
+                writer.write_all(&[1])?;
+                writer.write_all(&sigma.to_le_bytes())?;
+                writer.write_all(&cutoff.to_le_bytes())?;
+            }
+        }
+        match &self.field_profile {
+            FieldProfile::None => writer.write_all(&[0])?,
+            FieldProfile::Uniform => writer.write_all(&[1])?,
+            FieldProfile::PerSite(fields) => {
+                writer.write_all(&[2])?;
+                for idx in &points {
+                    let field = fields.get(idx).copied().unwrap_or(0.0);
+                    writer.write_all(&field.to_le_bytes())?;
+                }
+            }
+        }
+        let accepted = self.accepted_flips.value_as::<u64>().unwrap();
+        writer.write_all(&accepted.to_le_bytes())?;
+        Ok(())
+    }
+
+    fn read_checkpoint_settings<R: Read>(&mut self, reader: &mut R) -> Result<(), JikiError> {
+        let points = self.lattice.points_in_order();
+        let mut packed = vec![0u8; points.len().div_ceil(8)];
+        reader.read_exact(&mut packed)?;
+        for (idx, vacant) in points.iter().zip(unpack(&packed, points.len())) {
+            if vacant {
+                self.spins.remove(idx);
+            }
+        }
+        reader.read_exact(&mut packed)?;
+        for (idx, pinned) in points.iter().zip(unpack(&packed, points.len())) {
+            if pinned {
+                self.pinned.insert(idx.clone());
+            }
+        }
+        for bc in self.boundary.iter_mut() {
+            *bc = match read_u8(reader)? {
+                0 => BoundaryCondition::Open,
+                1 => BoundaryCondition::Periodic,
+                _ => return Err(JikiError::InvalidCheckpoint),
+            };
+        }
+        self.metric = match read_u8(reader)? {
+            0 => Metric::Manhattan,
+            1 => Metric::Chebyshev,
+            2 => Metric::Euclidean,
+            _ => return Err(JikiError::InvalidCheckpoint),
+        };
+        match read_u8(reader)? {
+            0 => self.interaction = Interaction::NearestNeighbor,
+            1 => {
+                let sigma = read_f64(reader)?;
+                let cutoff = read_f64(reader)?;
+                if cutoff.is_nan() || cutoff < 1.0 {
+                    return Err(JikiError::InvalidCheckpoint);
+                }
+                self.set_long_range(sigma, cutoff);
+            }
+            _ => return Err(JikiError::InvalidCheckpoint),
+        }
+        self.field_profile = match read_u8(reader)? {
+            0 => FieldProfile::None,
+            1 => FieldProfile::Uniform,
+            2 => FieldProfile::PerSite(
+                points
+                    .iter()
+                    .map(|idx| Ok((idx.clone(), read_f64(reader)?)))
+                    .collect::<io::Result<_>>()?,
+            ),
+            _ => return Err(JikiError::InvalidCheckpoint),
+        };
+        let mut accepted = [0; 8];
+        reader.read_exact(&mut accepted)?;
+        self.accepted_flips = u64::from_le_bytes(accepted)
+            .value_as::<usize>()
+            .map_err(|_| JikiError::InvalidCheckpoint)?;
+        self.rebuild_topology();
+        Ok(())
+    }
+
+    pub fn load_checkpoint<R: Read>(mut reader: R) -> Result<Self, JikiError> {
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != CHECKPOINT_MAGIC {
+            return Err(JikiError::InvalidCheckpoint);
+        }
+        let mut version = [0; 1];
+        reader.read_exact(&mut version)?;
+        if !(1..=CHECKPOINT_VERSION).contains(&version[0]) {
+            return Err(JikiError::UnsupportedVersion(version[0]));
+        }
+        let mut read_usize = || -> Result<usize, JikiError> {
+            usize::try_from(read_u32(&mut reader)?).map_err(|_| JikiError::InvalidCheckpoint)
+        };
+        // Header values are untrusted, so nothing is allocated from them up front: the sizes
+        // are pushed as they are read and the spin bits only grow as far as the input goes.
+        let dimension = read_usize()?;
+        let mut size = Vec::new();
+        for _ in 0..dimension {
+            size.push(read_usize()?);
+        }
+        let sites = size
+            .iter()
+            .try_fold(1usize, |sites, &extent| sites.checked_mul(extent))
+            .ok_or(JikiError::InvalidCheckpoint)?;
+        let mut lattice = Lattice::new(dimension);
+        lattice.set_size(size);
+        let packed_len = sites.div_ceil(8);
+        let mut packed = Vec::new();
+        (&mut reader)
+            .take(packed_len.value_as::<u64>().unwrap())
+            .read_to_end(&mut packed)?;
+        if packed.len() != packed_len {
+            return Err(JikiError::InvalidCheckpoint);
+        }
+        let coupling = read_f64(&mut reader)?;
+        let applied_field = read_f64(&mut reader)?;
+        let temperature = read_f64(&mut reader)?;
+        let mut ising = Ising::try_new(lattice, coupling, applied_field, temperature)?;
+        let points = ising.lattice.points_in_order();
+        for (idx, up) in points.iter().zip(unpack(&packed, points.len())) {
+            let spin = if up { Spin::Up } else { Spin::Down };
+            ising.set_spin(idx, spin).unwrap();
+        }
+        if version[0] >= 2 {
+            let mut seed = [0; 32];
+            reader.read_exact(&mut seed)?;
+            let mut stream = [0; 8];
+            reader.read_exact(&mut stream)?;
+            let mut word_pos = [0; 16];
+            reader.read_exact(&mut word_pos)?;
+            ising.rng = ChaCha12Rng::from_seed(seed);
+            ising.rng.set_stream(u64::from_le_bytes(stream));
+            ising.rng.set_word_pos(u128::from_le_bytes(word_pos));
+        }
+        if version[0] >= 3 {
+            ising.read_checkpoint_settings(&mut reader)?;
+        }
+        Ok(ising)
+    }
+
+    pub fn write_npy<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let shape = match self.lattice.size.as_slice() {
+            [n] => format!("({},)", n),
+            size => format!(
+                "({})",
+                size.iter()
+                    .map(|n| n.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        };
+        let mut header = format!(
+            "{{'descr': '|i1', 'fortran_order': False, 'shape': {}, }}",
+            shape
+        );
+        let unpadded = 10 + header.len() + 1;
+        header.push_str(&" ".repeat((64 - unpadded % 64) % 64));
+        header.push('\n');
+        writer.write_all(b"\x93NUMPY")?;
+        writer.write_all(&[1, 0])?;
+        writer.write_all(&(header.len() as u16).to_le_bytes())?;
+        writer.write_all(header.as_bytes())?;
+        writer.write_all(&self.configuration_with_vacancies(1i8 as u8, -1i8 as u8, 0))
+    }
+
+    pub fn write_pgm<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let (rows, columns) = match *self.lattice.size.as_slice() {
+            [rows, columns] => (rows, columns),
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "PGM export requires a 2 dimensional lattice",
+                ))
+            }
+        };
+        write!(writer, "P5\n{} {}\n255\n", columns, rows)?;
+        writer.write_all(&self.configuration_with_vacancies(255u8, 0u8, 128u8))
+    }
+
+    pub fn run_with_frames(
+        &mut self,
+        sweeps: usize,
+        interval: usize,
+        dir: &Path,
+    ) -> io::Result<()> {
+        assert!(interval > 0, "frame interval must be positive");
+        if self.lattice.dimension != 2 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "PGM frames require a 2 dimensional lattice",
+            ));
+        }
+        for s in 1..=sweeps {
+            self.sweep();
+            if s % interval == 0 {
+                let path = dir.join(format!("frame_{:05}.pgm", s / interval));
+                let mut writer = BufWriter::new(File::create(path)?);
+                self.write_pgm(&mut writer)?;
+                writer.flush()?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn write_vtk<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let (nx, ny, nz) = match *self.lattice.size.as_slice() {
+            [nx, ny] => (nx, ny, 1),
+            [nx, ny, nz] => (nx, ny, nz),
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "VTK export requires a 2 or 3 dimensional lattice",
+                ))
+            }
+        };
+        writeln!(writer, "# vtk DataFile Version 3.0")?;
+        writeln!(writer, "Jiki Ising configuration")?;
+        writeln!(writer, "ASCII")?;
+        writeln!(writer, "DATASET STRUCTURED_POINTS")?;
+        writeln!(writer, "DIMENSIONS {} {} {}", nx, ny, nz)?;
+        writeln!(writer, "ORIGIN 0 0 0")?;
+        writeln!(writer, "SPACING 1 1 1")?;
+        writeln!(writer, "POINT_DATA {}", nx * ny * nz)?;
+        writeln!(writer, "SCALARS spin int 1")?;
+        writeln!(writer, "LOOKUP_TABLE default")?;
+        for z in 0..nz {
+            for y in 0..ny {
+                for x in 0..nx {
+                    let idx = if self.lattice.dimension == 2 {
+                        vec![x, y]
+                    } else {
+                        vec![x, y, z]
+                    };
+                    let value = self.spins.get(&idx).map_or(0.0, |spin| spin.value());
+                    writeln!(writer, "{}", value)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "ndarray")]
+impl Ising {
+    pub fn to_ndarray(&self) -> ArrayD<i8> {
+        ArrayD::from_shape_vec(
+            IxDyn(&self.lattice.size),
+            self.configuration_with_vacancies(1, -1, 0),
+        )
+        .unwrap()
+    }
+
+    /// Zero entries become vacancies, as written by `to_ndarray`.
+    pub fn from_ndarray(
+        array: &ArrayD<i8>,
+        coupling: f64,
+        applied_field: f64,
+        temperature: f64,
+    ) -> Self {
+        let mut lattice = Lattice::new(array.ndim());
+        lattice.set_size(array.shape().to_vec());
+        let mut ising = Ising::new(lattice, coupling, applied_field, temperature);
+        for (idx, &value) in array.indexed_iter() {
+            match value.signum() {
+                1 => ising.set_spin(idx.slice(), Spin::Up).unwrap(),
+                -1 => ising.set_spin(idx.slice(), Spin::Down).unwrap(),
+                _ => {
+                    ising.spins.remove(idx.slice());
+                }
+            }
+        }
+        ising.rebuild_topology();
+        ising
+    }
+}
+
+#[cfg(feature = "image")]
+impl Ising {
+    /// Grey pixels (128) become vacancies, as written by `write_pgm`.
+    pub fn from_image<P: AsRef<Path>>(
+        path: P,
+        coupling: f64,
+        applied_field: f64,
+        temperature: f64,
+    ) -> Result<Self, image::ImageError> {
+        let image = image::open(path)?.into_luma8();
+        let (columns, rows) = image.dimensions();
+        let mut lattice = Lattice::new(2);
+        lattice.set_size(vec![rows as usize, columns as usize]);
+        let mut ising = Ising::new(lattice, coupling, applied_field, temperature);
+        for (x, y, pixel) in image.enumerate_pixels() {
+            let idx = [y as usize, x as usize];
+            match pixel.0[0].cmp(&128) {
+                std::cmp::Ordering::Greater => ising.set_spin(&idx, Spin::Up).unwrap(),
+                std::cmp::Ordering::Less => ising.set_spin(&idx, Spin::Down).unwrap(),
+                std::cmp::Ordering::Equal => {
+                    ising.spins.remove(idx.as_slice());
+                }
+            }
+        }
+        ising.rebuild_topology();
+        Ok(ising)
+    }
+}
+
+#[cfg(feature = "config")]
+fn config_value<'a>(table: &'a toml::Table, key: &str) -> Result<&'a toml::Value, JikiError> {
+    table
+        .get(key)
+        .ok_or_else(|| JikiError::InvalidConfig(format!("missing key `{}`", key)))
+}
+
+#[cfg(feature = "config")]
+fn config_float(table: &toml::Table, key: &str) -> Result<f64, JikiError> {
+    match config_value(table, key)? {
+        toml::Value::Float(value) => Ok(*value),
+        toml::Value::Integer(value) => Ok(*value as f64),
+        _ => Err(JikiError::InvalidConfig(format!(
+            "`{}` must be a number",
+            key
+        ))),
+    }
+}
+
+#[cfg(feature = "config")]
+fn config_usize(value: &toml::Value, key: &str) -> Result<usize, JikiError> {
+    value
+        .as_integer()
+        .and_then(|value| usize::try_from(value).ok())
+        .ok_or_else(|| {
+            JikiError::InvalidConfig(format!("`{}` must be a non-negative integer", key))
+        })
+}
+
+#[cfg(feature = "config")]
+fn config_boundary(value: &toml::Value) -> Result<BoundaryCondition, JikiError> {
+    match value.as_str().map(str::to_lowercase).as_deref() {
+        Some("open") => Ok(BoundaryCondition::Open),
+        Some("periodic") => Ok(BoundaryCondition::Periodic),
+        _ => Err(JikiError::InvalidConfig(
+            "`boundary` must be \"open\" or \"periodic\"".to_string(),
+        )),
+    }
+}
+
+#[cfg(feature = "config")]
+impl Ising {
+    pub fn from_config<P: AsRef<Path>>(path: P) -> Result<Self, JikiError> {
+        let text = std::fs::read_to_string(path)?;
+        let table: toml::Table = text
+            .parse()
+            .map_err(|error: toml::de::Error| JikiError::InvalidConfig(error.to_string()))?;
+        let dimension = config_usize(config_value(&table, "dimension")?, "dimension")?;
+        let size = config_value(&table, "size")?
+            .as_array()
+            .ok_or_else(|| JikiError::InvalidConfig("`size` must be an array".to_string()))?
+            .iter()
+            .map(|extent| config_usize(extent, "size"))
+            .collect::<Result<Vec<usize>, JikiError>>()?;
+        if size.len() != dimension {
+            return Err(JikiError::InvalidConfig(
+                "`size` does not match `dimension`".to_string(),
+            ));
+        }
+        let coupling = config_float(&table, "coupling")?;
+        let field = match table.get("field") {
+            Some(_) => config_float(&table, "field")?,
+            None => 0.0,
+        };
+        let temperature = config_float(&table, "temperature")?;
+        check_temperature(temperature)?;
+        let boundary = match table.get("boundary") {
+            None => vec![BoundaryCondition::Open; dimension],
+            Some(toml::Value::Array(axes)) => axes
+                .iter()
+                .map(config_boundary)
+                .collect::<Result<Vec<BoundaryCondition>, JikiError>>()?,
+            Some(value) => vec![config_boundary(value)?; dimension],
+        };
+        if boundary.len() != dimension {
+            return Err(JikiError::InvalidConfig(
+                "`boundary` does not match `dimension`".to_string(),
+            ));
+        }
+        let mut lattice = Lattice::new(dimension);
+        lattice.set_size(size);
+        let mut ising = match table.get("seed") {
+            Some(seed) => {
+                let seed = seed
+                    .as_integer()
+                    .and_then(|seed| u64::try_from(seed).ok())
+                    .ok_or_else(|| {
+                        JikiError::InvalidConfig(
+                            "`seed` must be a non-negative integer".to_string(),
+                        )
+                    })?;
+                Ising::with_seed(lattice, coupling, field, temperature, seed)
+            }
+            None => Ising::new(lattice, coupling, field, temperature),
+        };
+        ising.set_boundary(boundary);
+        Ok(ising)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn model(size: &[usize], seed: u64) -> Ising {
+        let mut lattice = Lattice::new(size.len());
+        lattice.set_size(size.to_vec());
+        let mut ising = Ising::with_seed(lattice, 1.0, 0.0, 2.0 / BOLTZMANN, seed);
+        ising.randomize();
+        ising
+    }
+
+    #[test]
+    fn npy_header_reports_shape() {
+        let ising = model(&[3, 5], 2);
+        let mut bytes = Vec::new();
+        ising.write_npy(&mut bytes).unwrap();
+        assert_eq!(&bytes[..6], b"\x93NUMPY");
+        assert_eq!(&bytes[6..8], &[1, 0]);
+        let header_len = usize::from(u16::from_le_bytes([bytes[8], bytes[9]]));
+        assert_eq!((10 + header_len) % 64, 0);
+        let header = std::str::from_utf8(&bytes[10..10 + header_len]).unwrap();
+        assert!(header.contains("'descr': '|i1'"));
+        assert!(header.contains("'shape': (3, 5)"));
+        let data = &bytes[10 + header_len..];
+        assert_eq!(data.len(), 15);
+        for (n, idx) in ising.lattice.points_in_order().iter().enumerate() {
+            let value = f64::from(i8::from_le_bytes([data[n]]));
+            assert_eq!(value, ising.get_spin(idx).unwrap().value());
+        }
+    }
+
+    #[cfg(feature = "ndarray")]
+    #[test]
+    fn ndarray_round_trip_preserves_spins() {
+        let ising = model(&[3, 4, 2], 1);
+        let array = ising.to_ndarray();
+        assert_eq!(array.shape(), &[3, 4, 2]);
+        for idx in ising.lattice.all_points() {
+            let value = f64::from(array[idx.as_slice()]);
+            assert_eq!(value, ising.get_spin(&idx).unwrap().value());
+        }
+        let restored = Ising::from_ndarray(&array, 1.0, 0.0, 2.0 / BOLTZMANN);
+        assert!(restored.spins == ising.spins);
+    }
+
+    #[test]
+    fn vtk_header_matches_lattice() {
+        for (size, dimensions) in [(vec![2, 3, 4], "2 3 4"), (vec![3, 5], "3 5 1")] {
+            let ising = model(&size, 3);
+            let mut bytes = Vec::new();
+            ising.write_vtk(&mut bytes).unwrap();
+            let text = String::from_utf8(bytes).unwrap();
+            let lines: Vec<&str> = text.lines().collect();
+            assert_eq!(lines[0], "# vtk DataFile Version 3.0");
+            assert_eq!(lines[3], "DATASET STRUCTURED_POINTS");
+            assert_eq!(lines[4], format!("DIMENSIONS {}", dimensions));
+            let sites = ising.num_sites();
+            assert_eq!(lines[7], format!("POINT_DATA {}", sites));
+            assert_eq!(lines.len() - 10, sites);
+            assert!(lines[10..].iter().all(|line| *line == "1" || *line == "-1"));
+        }
+        assert!(model(&[4], 3).write_vtk(Vec::new()).is_err());
+    }
+
+    #[cfg(feature = "image")]
+    #[test]
+    fn pgm_round_trips_through_from_image() {
+        let ising = model(&[5, 7], 4);
+        let path = std::env::temp_dir().join(format!("jiki_round_trip_{}.pgm", std::process::id()));
+        let mut writer = BufWriter::new(File::create(&path).unwrap());
+        ising.write_pgm(&mut writer).unwrap();
+        writer.flush().unwrap();
+        drop(writer);
+        let restored = Ising::from_image(&path, 1.0, 0.0, 2.0 / BOLTZMANN).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(restored.lattice.size, vec![5, 7]);
+        assert!(restored.spins == ising.spins);
+    }
+
+    #[test]
+    fn diluted_exports_mark_vacancies() {
+        let mut lattice = Lattice::new(2);
+        lattice.set_size(vec![3, 4]);
+        let mut ising = Ising::with_dilution(lattice, 1.0, 0.0, 2.0 / BOLTZMANN, 0.4, 6);
+        ising.randomize();
+        let occupied = ising.num_sites();
+        assert!(occupied > 0 && occupied < 12);
+        let vacancies = 12 - occupied;
+
+        assert_eq!(ising.pack_bits().len(), occupied.div_ceil(8));
+        let mut restored = ising.clone();
+        restored.reset(Spin::Down);
+        restored.unpack_bits(&ising.pack_bits()).unwrap();
+        assert!(restored.spins == ising.spins);
+
+        let mut npy = Vec::new();
+        ising.write_npy(&mut npy).unwrap();
+        assert_eq!(
+            npy.iter().rev().take(12).filter(|&&b| b == 0).count(),
+            vacancies
+        );
+        let mut pgm = Vec::new();
+        ising.write_pgm(&mut pgm).unwrap();
+        assert_eq!(
+            pgm.iter().rev().take(12).filter(|&&b| b == 128).count(),
+            vacancies
+        );
+        let mut vtk = Vec::new();
+        ising.write_vtk(&mut vtk).unwrap();
+        let text = String::from_utf8(vtk).unwrap();
+        assert_eq!(
+            text.lines().skip(10).filter(|line| *line == "0").count(),
+            vacancies
+        );
+        let mut checkpoint = Vec::new();
+        ising.save_checkpoint(&mut checkpoint).unwrap();
+        let loaded = Ising::load_checkpoint(checkpoint.as_slice()).unwrap();
+        assert!(loaded.spins == ising.spins);
+        assert_eq!(
+            loaded.sites().collect::<Vec<_>>(),
+            ising.sites().collect::<Vec<_>>()
+        );
+        assert_eq!(loaded.topology.basis_len(), ising.topology.basis_len());
+    }
+
+    #[cfg(feature = "ndarray")]
+    #[test]
+    fn diluted_ndarray_has_zero_at_vacancies() {
+        let mut lattice = Lattice::new(2);
+        lattice.set_size(vec![3, 4]);
+        let mut ising = Ising::with_dilution(lattice, 1.0, 0.0, 2.0 / BOLTZMANN, 0.4, 6);
+        let array = ising.to_ndarray();
+        for idx in ising.lattice.all_points() {
+            let expected = ising.spins.get(&idx).map_or(0, |_| 1);
+            assert_eq!(array[idx.as_slice()], expected);
+        }
+        ising.randomize();
+        let restored = Ising::from_ndarray(&ising.to_ndarray(), 1.0, 0.0, 2.0 / BOLTZMANN);
+        assert!(restored.spins == ising.spins);
+        assert_eq!(restored.topology.basis_len(), ising.topology.basis_len());
+    }
+
+    #[cfg(feature = "image")]
+    #[test]
+    fn diluted_pgm_round_trips_vacancies() {
+        let mut lattice = Lattice::new(2);
+        lattice.set_size(vec![4, 5]);
+        let mut ising = Ising::with_dilution(lattice, 1.0, 0.0, 2.0 / BOLTZMANN, 0.3, 8);
+        ising.randomize();
+        assert!(ising.num_sites() < 20);
+        let path = std::env::temp_dir().join(format!("jiki_diluted_{}.pgm", std::process::id()));
+        let mut writer = BufWriter::new(File::create(&path).unwrap());
+        ising.write_pgm(&mut writer).unwrap();
+        writer.flush().unwrap();
+        drop(writer);
+        let restored = Ising::from_image(&path, 1.0, 0.0, 2.0 / BOLTZMANN).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(restored.spins == ising.spins);
+        assert_eq!(restored.num_sites(), ising.num_sites());
+    }
+
+    #[test]
+    fn checkpoint_round_trips_and_rejects_unknown_versions() {
+        let ising = model(&[3, 5], 7);
+        let mut bytes = Vec::new();
+        ising.save_checkpoint(&mut bytes).unwrap();
+        let restored = Ising::load_checkpoint(bytes.as_slice()).unwrap();
+        assert_eq!(restored.lattice.size, vec![3, 5]);
+        assert!(restored.spins == ising.spins);
+        assert_eq!(restored.coupling, ising.coupling);
+        assert_eq!(restored.temperature, ising.temperature);
+
+        bytes[4] = CHECKPOINT_VERSION + 1;
+        let result = Ising::load_checkpoint(bytes.as_slice());
+        assert_eq!(
+            result.err(),
+            Some(JikiError::UnsupportedVersion(CHECKPOINT_VERSION + 1))
+        );
+        bytes[0] = b'X';
+        let result = Ising::load_checkpoint(bytes.as_slice());
+        assert_eq!(result.err(), Some(JikiError::InvalidCheckpoint));
+    }
+
+    #[test]
+    fn resuming_from_a_checkpoint_matches_an_uninterrupted_run() {
+        let mut lattice = Lattice::new(2);
+        lattice.set_size(vec![5, 6]);
+        let mut ising = Ising::with_dilution(lattice, 1.0, 0.1, 2.5 / BOLTZMANN, 0.2, 13);
+        ising.randomize();
+        ising.set_boundary(vec![BoundaryCondition::Periodic, BoundaryCondition::Open]);
+        ising.metric = Metric::Chebyshev;
+        ising.set_staggered_field(0.3);
+        ising.pin_faces(0);
+        for _ in 0..3 {
+            ising.sweep();
+        }
+
+        let mut bytes = Vec::new();
+        ising.save_checkpoint(&mut bytes).unwrap();
+        let mut resumed = Ising::load_checkpoint(bytes.as_slice()).unwrap();
+        assert!(resumed.boundary == ising.boundary);
+        assert!(resumed.pinned == ising.pinned);
+        assert!(resumed.field_profile == ising.field_profile);
+        assert!(resumed.interaction == ising.interaction);
+        assert!(resumed.metric == ising.metric);
+        assert_eq!(resumed.topology.basis_len(), ising.topology.basis_len());
+
+        for _ in 0..5 {
+            ising.sweep();
+            resumed.sweep();
+        }
+        assert!(resumed.spins == ising.spins);
+        assert_eq!(resumed.total_accepted_flips(), ising.total_accepted_flips());
+
+        ising.set_long_range(1.5, 2.0);
+        let mut bytes = Vec::new();
+        ising.save_checkpoint(&mut bytes).unwrap();
+        let resumed = Ising::load_checkpoint(bytes.as_slice()).unwrap();
+        assert!(resumed.interaction == ising.interaction);
+        assert_eq!(
+            resumed.interacting_sites(&[1, 1]),
+            ising.interacting_sites(&[1, 1])
+        );
+
+        let range = [1.5f64.to_le_bytes(), 2.0f64.to_le_bytes()].concat();
+        let at = bytes.windows(16).position(|w| w == range).unwrap();
+        bytes[at + 8..at + 16].copy_from_slice(&0.5f64.to_le_bytes());
+        assert!(matches!(
+            Ising::load_checkpoint(bytes.as_slice()),
+            Err(JikiError::InvalidCheckpoint)
+        ));
+    }
+
+    #[test]
+    fn pack_bits_round_trips_a_partial_final_byte() {
+        let ising = model(&[3, 5], 8);
+        let packed = ising.pack_bits();
+        assert_eq!(packed.len(), 2);
+        assert_eq!(packed[1] >> 7, 0);
+        let mut restored = ising.clone();
+        restored.reset(Spin::Up);
+        restored.unpack_bits(&packed).unwrap();
+        assert!(restored.spins == ising.spins);
+        assert_eq!(
+            restored.unpack_bits(&packed[..1]),
+            Err(JikiError::ShapeMismatch)
+        );
+    }
+
+    #[cfg(feature = "config")]
+    #[test]
+    fn config_parses_a_sample_and_rejects_negative_seeds() {
+        let path = std::env::temp_dir().join(format!("jiki_sample_{}.toml", std::process::id()));
+        let sample = "dimension = 2\nsize = [4, 6]\ncoupling = 1.0\nfield = 0.5\n\
+                      temperature = 2\nboundary = \"periodic\"\nseed = 9\n";
+        std::fs::write(&path, sample).unwrap();
+        let ising = Ising::from_config(&path).unwrap();
+        assert_eq!(ising.lattice.size, vec![4, 6]);
+        assert_eq!(ising.coupling, 1.0);
+        assert_eq!(ising.applied_field, 0.5);
+        assert_eq!(ising.temperature(), 2.0);
+        assert_eq!(ising.boundary, vec![BoundaryCondition::Periodic; 2]);
+        std::fs::write(&path, sample.replace("seed = 9", "seed = -1")).unwrap();
+        let negative = Ising::from_config(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(negative.err(), Some(JikiError::InvalidConfig(_))));
+    }
+
+    #[test]
+    fn rle_round_trips_and_collapses_a_uniform_lattice() {
+        let ising = model(&[4, 5], 10);
+        let runs = ising.rle_encode();
+        assert_eq!(runs.iter().map(|(_, length)| length).sum::<usize>(), 20);
+        assert!(runs.windows(2).all(|pair| pair[0].0 != pair[1].0));
+        let mut restored = model(&[4, 5], 11);
+        restored.rle_decode(&runs).unwrap();
+        assert!(restored.spins == ising.spins);
+        restored
+            .spins
+            .values_mut()
+            .for_each(|spin| *spin = Spin::Down);
+        let single = restored.rle_encode();
+        assert_eq!(single.len(), 1);
+        assert!(single[0] == (Spin::Down, 20));
+        assert!(matches!(
+            restored.rle_decode(&[(Spin::Up, 19)]).err(),
+            Some(JikiError::ShapeMismatch)
+        ));
+    }
+
+    #[test]
+    fn run_with_frames_writes_one_frame_per_interval() {
+        let dir = std::env::temp_dir().join(format!("jiki_frames_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut ising = model(&[4, 4], 12);
+        ising.run_with_frames(10, 3, &dir).unwrap();
+        let mut frames: Vec<String> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        frames.sort();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(
+            frames,
+            vec!["frame_00001.pgm", "frame_00002.pgm", "frame_00003.pgm"]
+        );
+        assert!(model(&[4], 12)
+            .run_with_frames(1, 1, Path::new("."))
+            .is_err());
+    }
+
+    #[test]
+    fn hostile_checkpoint_headers_error_without_allocating() {
+        let header = |dimension: u32, sizes: &[u32]| {
+            let mut bytes = b"JIKI".to_vec();
+            bytes.push(3);
+            bytes.extend(dimension.to_le_bytes());
+            for size in sizes {
+                bytes.extend(size.to_le_bytes());
+            }
+            bytes
+        };
+        let overflowing = header(3, &[u32::MAX; 3]);
+        assert!(matches!(
+            Ising::load_checkpoint(overflowing.as_slice()).err(),
+            Some(JikiError::InvalidCheckpoint)
+        ));
+        let huge = header(2, &[1 << 20, 1 << 20]);
+        assert!(matches!(
+            Ising::load_checkpoint(huge.as_slice()).err(),
+            Some(JikiError::InvalidCheckpoint)
+        ));
+        let endless = header(u32::MAX, &[4, 4]);
+        assert!(Ising::load_checkpoint(endless.as_slice()).is_err());
+    }
+}
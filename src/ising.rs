@@ -1,19 +1,115 @@
 use conv::prelude::*;
 use itertools::Itertools;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
 
-use rand::Rng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha12Rng;
 
+use crate::analysis::{least_squares_slope, DensityOfStates};
+use crate::dynamics::*;
+use crate::error::JikiError;
 use crate::topology::*;
 
 pub const BOLTZMANN: f64 = 1.380649e-23;
 
+pub const MAX_ENUMERATION_SITES: usize = 20;
+
 #[derive(Clone, Copy, PartialEq)]
 pub enum Spin {
     Up,
     Down,
 }
 
+impl Spin {
+    pub fn value(self) -> f64 {
+        match self {
+            Spin::Up => 1.0,
+            Spin::Down => -1.0,
+        }
+    }
+
+    pub fn flipped(self) -> Spin {
+        match self {
+            Spin::Up => Spin::Down,
+            Spin::Down => Spin::Up,
+        }
+    }
+}
+
+impl fmt::Display for Spin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self, f.alternate()) {
+            (Spin::Up, false) => write!(f, "+"),
+            (Spin::Down, false) => write!(f, "-"),
+            (Spin::Up, true) => write!(f, "↑"),
+            (Spin::Down, true) => write!(f, "↓"),
+        }
+    }
+}
+
+impl FromStr for Spin {
+    type Err = JikiError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "+" | "up" | "1" | "+1" | "↑" => Ok(Spin::Up),
+            "-" | "down" | "-1" | "↓" => Ok(Spin::Down),
+            _ => Err(JikiError::InvalidSpin(s.to_string())),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Metric {
+    Manhattan,
+    Chebyshev,
+    Euclidean,
+}
+
+impl Metric {
+    pub fn distance(&self, a: &[usize], b: &[usize]) -> f64 {
+        self.combine(a.iter().zip(b).map(|(&x, &y)| abs_distance(x, y)))
+    }
+
+    pub fn combine(&self, diffs: impl Iterator<Item = usize>) -> f64 {
+        match self {
+            Metric::Manhattan => diffs.sum::<usize>().value_as::<f64>().unwrap(),
+            Metric::Chebyshev => diffs.max().unwrap_or(0).value_as::<f64>().unwrap(),
+            Metric::Euclidean => diffs
+                .map(|d| d * d)
+                .sum::<usize>()
+                .value_as::<f64>()
+                .unwrap()
+                .sqrt(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum BoundaryCondition {
+    Open,
+    Periodic,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum FieldProfile {
+    None,
+    Uniform,
+    PerSite(HashMap<LatticePoint, f64>),
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Interaction {
+    NearestNeighbor,
+    PowerLaw { sigma: f64, cutoff: f64 },
+}
+
+pub type Bias = Arc<dyn Fn(&Ising) -> f64 + Send + Sync>;
+
 #[derive(Clone)]
 pub struct Lattice {
     pub dimension: usize,
@@ -36,195 +132,3196 @@ impl Lattice {
         self.size = size;
     }
 
+    /// Iterates points in row-major order: the last coordinate varies fastest.
     pub fn all_points(&self) -> impl Iterator<Item = LatticePoint> + '_ {
         (0..self.dimension)
             .map(|d| 0..self.size[d])
             .multi_cartesian_product()
     }
+
+    /// Row-major order with the last coordinate varying fastest, e.g. for a `[2, 3]`
+    /// lattice: `[0, 0], [0, 1], [0, 2], [1, 0], [1, 1], [1, 2]`. Serialized
+    /// configurations rely on this order, so it is fixed independently of `all_points`.
+    pub fn points_in_order(&self) -> Vec<LatticePoint> {
+        let total: usize = self.size.iter().product();
+        let mut points = Vec::with_capacity(total);
+        let mut idx = vec![0; self.dimension];
+        for _ in 0..total {
+            points.push(idx.clone());
+            for d in (0..self.dimension).rev() {
+                idx[d] += 1;
+                if idx[d] < self.size[d] {
+                    break;
+                }
+                idx[d] = 0;
+            }
+        }
+        points
+    }
+
+    /// Sites at distance one from `idx` under `metric`, in `all_points` order. Periodic axes
+    /// wrap; the point itself is never its own neighbour, even on axes of length one or two.
+    pub fn neighbors(
+        &self,
+        idx: &[usize],
+        boundary: &[BoundaryCondition],
+        metric: Metric,
+    ) -> Vec<LatticePoint> {
+        let axes = self
+            .size
+            .iter()
+            .enumerate()
+            .zip(boundary)
+            .map(|((d, &cap), bc)| {
+                let periodic = *bc == BoundaryCondition::Periodic;
+                let forward = if idx[d] + 1 < cap {
+                    Some(idx[d] + 1)
+                } else {
+                    periodic.then_some(0)
+                };
+                let backward = if idx[d] > 0 {
+                    Some(idx[d] - 1)
+                } else {
+                    periodic.then(|| cap - 1)
+                };
+                std::iter::once((idx[d], 0))
+                    .chain(forward.into_iter().chain(backward).map(|c| (c, 1)))
+                    .collect::<Vec<(usize, usize)>>()
+            });
+        let mut neighbors: Vec<LatticePoint> = axes
+            .multi_cartesian_product()
+            .filter(|steps| metric.combine(steps.iter().map(|&(_, step)| step)) == 1.0)
+            .map(|steps| {
+                steps
+                    .into_iter()
+                    .map(|(coordinate, _)| coordinate)
+                    .collect()
+            })
+            .filter(|neighbor: &LatticePoint| neighbor != idx)
+            .collect();
+        neighbors.sort();
+        neighbors.dedup();
+        neighbors
+    }
+
+    // `idx` moved by `offset`, wrapping periodic axes and dropping sites past an open edge.
+    pub(crate) fn shifted(
+        &self,
+        idx: &[usize],
+        offset: &[isize],
+        boundary: &[BoundaryCondition],
+    ) -> Option<LatticePoint> {
+        idx.iter()
+            .zip(offset)
+            .zip(&self.size)
+            .zip(boundary)
+            .map(|(((&i, &step), &cap), bc)| {
+                let cap = cap.value_as::<isize>().unwrap();
+                let target = i.value_as::<isize>().unwrap() + step;
+                if (0..cap).contains(&target) {
+                    target.value_as::<usize>().ok()
+                } else if *bc == BoundaryCondition::Periodic {
+                    target.rem_euclid(cap).value_as::<usize>().ok()
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    pub fn distance(
+        &self,
+        a: &[usize],
+        b: &[usize],
+        boundary: &[BoundaryCondition],
+        metric: Metric,
+    ) -> f64 {
+        metric.combine(a.iter().zip(b).zip(&self.size).zip(boundary).map(
+            |(((&x, &y), &cap), bc)| {
+                let d = abs_distance(x, y);
+                match bc {
+                    BoundaryCondition::Open => d,
+                    BoundaryCondition::Periodic => d.min(cap - d),
+                }
+            },
+        ))
+    }
+}
+
+// Sites grouped by the energy change of flipping them, keyed by its bit pattern so that
+// equal changes share one class.
+#[derive(Clone, Default)]
+pub struct FlipRateClasses {
+    classes: BTreeMap<u64, Vec<LatticePoint>>,
+    slots: HashMap<LatticePoint, (u64, usize)>,
+}
+
+impl FlipRateClasses {
+    fn insert(&mut self, idx: LatticePoint, energy_change: f64) {
+        let key = energy_change.to_bits();
+        let sites = self.classes.entry(key).or_default();
+        self.slots.insert(idx.clone(), (key, sites.len()));
+        sites.push(idx);
+    }
+
+    fn remove(&mut self, idx: &LatticePoint) {
+        let Some((key, slot)) = self.slots.remove(idx) else {
+            return;
+        };
+        let sites = self.classes.get_mut(&key).unwrap();
+        sites.swap_remove(slot);
+        match sites.get(slot) {
+            Some(moved) => {
+                self.slots.insert(moved.clone(), (key, slot));
+            }
+            None if sites.is_empty() => {
+                self.classes.remove(&key);
+            }
+            None => {}
+        }
+    }
+
+    pub fn energy_changes(&self) -> impl Iterator<Item = (f64, &[LatticePoint])> {
+        self.classes
+            .iter()
+            .map(|(&key, sites)| (f64::from_bits(key), sites.as_slice()))
+    }
 }
 
+#[derive(Clone)]
+pub struct SweepSamples {
+    pub temperature: f64,
+    pub mean_energy: f64,
+    pub mean_magnetization: f64,
+    pub energies: Vec<f64>,
+    pub magnetizations: Vec<f64>,
+}
+
+#[derive(Clone)]
 pub struct Ising {
     pub lattice: Lattice,
     pub spins: HashMap<Vec<usize>, Spin>,
     pub coupling: f64,
     pub applied_field: f64,
-    pub temperature: f64,
+    pub field_profile: FieldProfile,
+    pub interaction: Interaction,
+    stencil: Vec<Vec<isize>>,
+    pub(crate) temperature: f64,
     pub topology: Topology,
+    pub metric: Metric,
+    pub boundary: Vec<BoundaryCondition>,
+    pub pinned: HashSet<LatticePoint>,
+    bias: Option<Bias>,
+    pub(crate) accepted_flips: usize,
+    pub(crate) rng: ChaCha12Rng,
 }
 
 impl Ising {
     pub fn new(lattice: Lattice, coupling: f64, applied_field: f64, temperature: f64) -> Self {
+        Ising::try_new(lattice, coupling, applied_field, temperature)
+            .unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    pub fn try_new(
+        lattice: Lattice,
+        coupling: f64,
+        applied_field: f64,
+        temperature: f64,
+    ) -> Result<Self, JikiError> {
+        check_temperature(temperature)?;
         let spins = (0..lattice.dimension)
             .map(|d| 0..lattice.size[d])
             .multi_cartesian_product()
             .map(|idx| (idx, Spin::Up))
             .collect::<HashMap<Vec<usize>, Spin>>();
         let topology = Topology::new(lattice.clone());
-        Ising {
+        let boundary = vec![BoundaryCondition::Open; lattice.dimension];
+        Ok(Ising {
             lattice,
             spins,
             coupling,
             applied_field,
+            field_profile: FieldProfile::Uniform,
+            interaction: Interaction::NearestNeighbor,
+            stencil: Vec::new(),
             temperature,
             topology,
+            metric: Metric::Manhattan,
+            boundary,
+            pinned: HashSet::new(),
+            bias: None,
+            accepted_flips: 0,
+            rng: ChaCha12Rng::from_entropy(),
+        })
+    }
+
+    pub fn with_seed(
+        lattice: Lattice,
+        coupling: f64,
+        applied_field: f64,
+        temperature: f64,
+        seed: u64,
+    ) -> Self {
+        let mut ising = Ising::new(lattice, coupling, applied_field, temperature);
+        ising.rng = ChaCha12Rng::seed_from_u64(seed);
+        ising
+    }
+
+    pub fn with_magnetization(
+        lattice: Lattice,
+        coupling: f64,
+        applied_field: f64,
+        temperature: f64,
+        magnetization: f64,
+        seed: u64,
+    ) -> Result<Self, JikiError> {
+        if !(-1.0..=1.0).contains(&magnetization) {
+            return Err(JikiError::InvalidMagnetization);
         }
+        let mut ising = Ising::with_seed(lattice, coupling, applied_field, temperature, seed);
+        let sites = ising.spins.len();
+        let up_count = (1.0 + magnetization) * sites.value_as::<f64>().unwrap() / 2.0;
+        if (up_count - up_count.round()).abs() > 1e-9 {
+            return Err(JikiError::InvalidMagnetization);
+        }
+        let up_count = up_count.round().approx_as::<usize>().unwrap();
+        let mut points: Vec<LatticePoint> = ising.lattice.all_points().collect();
+        points.shuffle(&mut ising.rng);
+        for (n, idx) in points.into_iter().enumerate() {
+            let spin = if n < up_count { Spin::Up } else { Spin::Down };
+            ising.spins.insert(idx, spin);
+        }
+        Ok(ising)
     }
 
-    pub fn get_spin(&self, idx: &[usize]) -> Result<Spin, &str> {
-        if idx
-            .iter()
+    pub fn with_dilution(
+        lattice: Lattice,
+        coupling: f64,
+        applied_field: f64,
+        temperature: f64,
+        vacancy_probability: f64,
+        seed: u64,
+    ) -> Self {
+        let mut ising = Ising::with_seed(lattice, coupling, applied_field, temperature, seed);
+        for idx in ising.lattice.all_points() {
+            if ising.rng.gen::<f64>() < vacancy_probability {
+                ising.spins.remove(&idx);
+            }
+        }
+        ising.rebuild_topology();
+        ising
+    }
+
+    pub(crate) fn rebuild_topology(&mut self) {
+        self.topology = Topology::from_points(self.lattice.clone(), self.sites().collect());
+    }
+
+    pub fn sites(&self) -> impl Iterator<Item = LatticePoint> + '_ {
+        self.lattice
+            .all_points()
+            .filter(|idx| self.spins.contains_key(idx))
+    }
+
+    pub fn randomize(&mut self) {
+        let sites: Vec<LatticePoint> = self.sites().collect();
+        for idx in sites {
+            let spin = if self.rng.gen::<bool>() {
+                Spin::Up
+            } else {
+                Spin::Down
+            };
+            self.spins.insert(idx, spin);
+        }
+    }
+
+    pub fn reset(&mut self, spin: Spin) {
+        for value in self.spins.values_mut() {
+            *value = spin;
+        }
+        self.accepted_flips = 0;
+    }
+
+    pub fn set_from_profile(&mut self, profile: impl Fn(&LatticePoint) -> f64, rng: &mut impl Rng) {
+        let sites: Vec<LatticePoint> = self.sites().collect();
+        for idx in sites {
+            let up_probability = (1.0 + profile(&idx).clamp(-1.0, 1.0)) / 2.0;
+            let spin = if rng.gen::<f64>() < up_probability {
+                Spin::Up
+            } else {
+                Spin::Down
+            };
+            self.spins.insert(idx, spin);
+        }
+    }
+
+    pub fn set_domain_wall(&mut self, axis: usize) {
+        assert!(axis < self.lattice.dimension, "axis out of range");
+        let midpoint = self.lattice.size[axis] / 2;
+        let sites: Vec<LatticePoint> = self.sites().collect();
+        for idx in sites {
+            let spin = if idx[axis] < midpoint {
+                Spin::Up
+            } else {
+                Spin::Down
+            };
+            self.spins.insert(idx, spin);
+        }
+    }
+
+    pub fn pin_faces(&mut self, axis: usize) {
+        assert!(axis < self.lattice.dimension, "axis out of range");
+        let last = self.lattice.size[axis] - 1;
+        let faces: Vec<LatticePoint> = self
+            .sites()
+            .filter(|idx| idx[axis] == 0 || idx[axis] == last)
+            .collect();
+        self.pinned.extend(faces);
+    }
+
+    pub fn subregion(&self, origin: &[usize], extent: &[usize]) -> Ising {
+        assert!(
+            origin.len() == self.lattice.dimension && extent.len() == self.lattice.dimension,
+            "origin and extent must match dimension of lattice"
+        );
+        assert!(
+            origin
+                .iter()
+                .zip(extent)
+                .zip(&self.lattice.size)
+                .all(|((&o, &e), &cap)| o + e <= cap),
+            "subregion extends beyond the lattice"
+        );
+        let mut lattice = Lattice::new(self.lattice.dimension);
+        lattice.set_size(extent.to_vec());
+        let mut region = Ising::new(lattice, self.coupling, self.applied_field, self.temperature);
+        region.metric = self.metric;
+        if let Interaction::PowerLaw { sigma, cutoff } = self.interaction {
+            region.set_long_range(sigma, cutoff);
+        }
+        for (((bc, &e), &cap), &own) in region
+            .boundary
+            .iter_mut()
+            .zip(extent)
             .zip(&self.lattice.size)
-            .any(|(&i, &cap)| i >= cap)
+            .zip(&self.boundary)
         {
-            return Err("Invalid Index");
+            if e == cap {
+                *bc = own;
+            }
+        }
+        if self.field_profile == FieldProfile::None {
+            region.field_profile = FieldProfile::None;
         }
-        Ok(self.spins.get(&idx.to_vec()).unwrap().clone())
+        if let FieldProfile::PerSite(fields) = &self.field_profile {
+            let region_fields = region
+                .lattice
+                .all_points()
+                .map(|idx| {
+                    let global: LatticePoint =
+                        idx.iter().zip(origin).map(|(&i, &o)| i + o).collect();
+                    (idx, fields.get(&global).copied().unwrap_or(0.0))
+                })
+                .collect();
+            region.field_profile = FieldProfile::PerSite(region_fields);
+        }
+        for idx in region.lattice.all_points() {
+            let global: LatticePoint = idx.iter().zip(origin).map(|(&i, &o)| i + o).collect();
+            match self.get_spin(&global) {
+                Ok(spin) => region.spins.insert(idx.clone(), spin),
+                Err(_) => region.spins.remove(&idx),
+            };
+            if self.pinned.contains(&global) {
+                region.pinned.insert(idx);
+            }
+        }
+        region.rebuild_topology();
+        region
+    }
+
+    /// One value per occupied site in `points_in_order`; vacancies are skipped.
+    pub fn configuration_as<T: Clone>(&self, up: T, down: T) -> Vec<T> {
+        self.lattice
+            .points_in_order()
+            .into_iter()
+            .filter_map(|idx| match self.spins.get(&idx)? {
+                Spin::Up => Some(up.clone()),
+                Spin::Down => Some(down.clone()),
+            })
+            .collect()
+    }
+
+    /// One value per lattice point in `points_in_order`, with `vacant` at vacancies.
+    pub fn configuration_with_vacancies<T: Clone>(&self, up: T, down: T, vacant: T) -> Vec<T> {
+        self.lattice
+            .points_in_order()
+            .into_iter()
+            .map(|idx| match self.spins.get(&idx) {
+                Some(Spin::Up) => up.clone(),
+                Some(Spin::Down) => down.clone(),
+                None => vacant.clone(),
+            })
+            .collect()
     }
 
-    pub fn set_spin(&mut self, idx: &[usize], spin: Spin) -> Result<(), &str> {
+    pub fn get_spin(&self, idx: &[usize]) -> Result<Spin, JikiError> {
         if idx
             .iter()
             .zip(&self.lattice.size)
             .any(|(&i, &cap)| i >= cap)
         {
-            return Err("Invalid Index");
+            return Err(JikiError::InvalidIndex);
         }
-        self.spins.get(idx).replace(&spin);
-        Ok(())
+        self.spins.get(idx).copied().ok_or(JikiError::VacantSite)
     }
 
-    pub fn nearest_neighbor(&self, idx: &[usize]) -> Result<Vec<Vec<usize>>, &str> {
+    pub fn set_spin(&mut self, idx: &[usize], spin: Spin) -> Result<(), JikiError> {
         if idx
             .iter()
             .zip(&self.lattice.size)
             .any(|(&i, &cap)| i >= cap)
         {
-            return Err("Invalid Index");
+            return Err(JikiError::InvalidIndex);
         }
-        let mut neighbors: Vec<Vec<usize>> = self
-            .spins
-            .keys()
-            .filter(|&node| {
-                node.iter()
-                    .zip(idx)
-                    .map(|(&n, &i)| abs_distance(n, i))
-                    .sum::<usize>()
-                    == 1
+        match self.spins.get_mut(idx) {
+            Some(site) => *site = spin,
+            None => return Err(JikiError::VacantSite),
+        }
+        Ok(())
+    }
+
+    pub fn temperature(&self) -> f64 {
+        self.temperature
+    }
+
+    pub fn set_temperature(&mut self, temperature: f64) -> Result<(), JikiError> {
+        check_temperature(temperature)?;
+        self.temperature = temperature;
+        Ok(())
+    }
+
+    pub fn set_boundary(&mut self, boundary: Vec<BoundaryCondition>) {
+        assert!(
+            boundary.len() == self.lattice.dimension,
+            "boundary vector does not match dimension of lattice"
+        );
+        self.boundary = boundary;
+    }
+
+    pub fn set_bias(&mut self, f: impl Fn(&Ising) -> f64 + Send + Sync + 'static) {
+        self.bias = Some(Arc::new(f));
+    }
+
+    pub fn clear_bias(&mut self) {
+        self.bias = None;
+    }
+
+    pub fn bias_energy(&self) -> f64 {
+        self.bias.as_ref().map_or(0.0, |f| f(self))
+    }
+
+    // Samples drawn under a bias V are reweighted by exp(V / kT) to recover unbiased averages.
+    pub fn bias_weight(&self) -> f64 {
+        (self.bias_energy() / (BOLTZMANN * self.temperature)).exp()
+    }
+
+    pub fn umbrella_run(&mut self, center: f64, k: f64, sweeps: usize) -> Vec<f64> {
+        let previous = self.bias.take();
+        self.set_bias(move |ising| 0.5 * k * (ising.magnetization() - center).powi(2));
+        let series = (0..sweeps)
+            .map(|_| {
+                self.sweep();
+                self.magnetization()
+            })
+            .collect();
+        self.bias = previous;
+        series
+    }
+
+    pub fn set_long_range(&mut self, sigma: f64, cutoff: f64) {
+        assert!(cutoff >= 1.0, "cutoff must include nearest neighbors");
+        self.interaction = Interaction::PowerLaw { sigma, cutoff };
+        // Every metric is at least the largest step along one axis, so all sites within the
+        // cutoff lie in this box of offsets; `interacting_sites` measures each one exactly.
+        // Assigning `interaction` directly skips this, so go through here.
+        self.stencil = self
+            .lattice
+            .size
+            .iter()
+            .map(|&cap| {
+                let reach = cutoff
+                    .min(cap.saturating_sub(1).value_as::<f64>().unwrap())
+                    .floor();
+                let reach = reach.approx_as::<isize>().unwrap();
+                -reach..=reach
             })
-            .cloned()
+            .multi_cartesian_product()
+            .filter(|offset| offset.iter().any(|&step| step != 0))
             .collect();
-        Ok(neighbors)
     }
 
-    pub fn local_energy(&self, idx: &[usize]) -> Result<f64, &str> {
+    pub fn interacting_sites(&self, idx: &[usize]) -> Vec<(LatticePoint, f64)> {
+        match self.interaction {
+            Interaction::NearestNeighbor => self
+                .nearest_neighbor(idx)
+                .unwrap()
+                .into_iter()
+                .map(|nidx| (nidx, self.coupling))
+                .collect(),
+            Interaction::PowerLaw { sigma, cutoff } => {
+                let exponent = self.lattice.dimension.value_as::<f64>().unwrap() + sigma;
+                let mut sites: Vec<(LatticePoint, f64)> = self
+                    .stencil
+                    .iter()
+                    .filter_map(|offset| self.lattice.shifted(idx, offset, &self.boundary))
+                    .filter(|node| self.spins.contains_key(node))
+                    .filter_map(|node| {
+                        let r = self.distance(&node, idx);
+                        (r > 0.0 && r <= cutoff).then(|| (node, self.coupling / r.powf(exponent)))
+                    })
+                    .collect();
+                // Offsets that wrap all the way around a small periodic axis land on the same
+                // site twice.
+                sites.sort_by(|a, b| a.0.cmp(&b.0));
+                sites.dedup_by(|a, b| a.0 == b.0);
+                sites
+            }
+        }
+    }
+
+    pub fn distance(&self, a: &[usize], b: &[usize]) -> f64 {
+        self.lattice.distance(a, b, &self.boundary, self.metric)
+    }
+
+    pub fn nearest_neighbor(&self, idx: &[usize]) -> Result<Vec<Vec<usize>>, JikiError> {
         if idx
             .iter()
             .zip(&self.lattice.size)
             .any(|(&i, &cap)| i >= cap)
         {
-            return Err("Invalid Index");
+            return Err(JikiError::InvalidIndex);
         }
-        let local_spin = match self.get_spin(idx).unwrap() {
-            Spin::Up => 1.0,
-            Spin::Down => -1.0,
-        };
-        let field_energy = -self.applied_field * local_spin;
-        let neighbor_energy: f64 = self
-            .nearest_neighbor(idx)
-            .unwrap()
-            .iter()
-            .map(|nidx| {
-                let neighbor_spin = match self.spins.get(nidx).unwrap() {
-                    Spin::Up => 1.0,
-                    Spin::Down => -1.0,
+        Ok(self
+            .lattice
+            .neighbors(idx, &self.boundary, self.metric)
+            .into_iter()
+            .filter(|node| self.spins.contains_key(node))
+            .collect())
+    }
+
+    pub fn coordination_number(&self, idx: &[usize]) -> usize {
+        self.nearest_neighbor(idx).unwrap().len()
+    }
+
+    pub fn bulk_coordination_number(&self) -> usize {
+        let center: LatticePoint = self.lattice.size.iter().map(|&cap| cap / 2).collect();
+        self.coordination_number(&center)
+    }
+
+    pub fn field_at(&self, idx: &[usize]) -> f64 {
+        match &self.field_profile {
+            FieldProfile::None => 0.0,
+            FieldProfile::Uniform => self.applied_field,
+            FieldProfile::PerSite(fields) => {
+                self.applied_field + fields.get(idx).copied().unwrap_or(0.0)
+            }
+        }
+    }
+
+    pub fn set_staggered_field(&mut self, amplitude: f64) {
+        let fields = self
+            .lattice
+            .all_points()
+            .map(|idx| {
+                let sign = if idx.iter().sum::<usize>() % 2 == 0 {
+                    1.0
+                } else {
+                    -1.0
                 };
-                -neighbor_spin * local_spin * self.coupling
+                (idx, amplitude * sign)
             })
+            .collect();
+        self.field_profile = FieldProfile::PerSite(fields);
+    }
+
+    pub fn local_energy(&self, idx: &[usize]) -> Result<f64, JikiError> {
+        Ok(-self.get_spin(idx)?.value() * self.local_field(idx))
+    }
+
+    pub fn local_field(&self, idx: &[usize]) -> f64 {
+        let neighbor_field: f64 = self
+            .interacting_sites(idx)
+            .iter()
+            .map(|(nidx, coupling)| coupling * self.spins.get(nidx).unwrap().value())
             .sum();
-        Ok(field_energy + neighbor_energy)
+        self.field_at(idx) + neighbor_field
     }
 
-    pub fn total_energy(&self) -> f64 {
+    pub fn flip_energy_delta(&self, idx: &[usize]) -> Result<f64, JikiError> {
+        Ok(-2.0 * self.local_energy(idx)?)
+    }
+
+    pub fn flip_energy_deltas(&self) -> HashMap<LatticePoint, f64> {
         self.spins
-            .iter()
-            .map(|(idx, _)| self.local_energy(idx).unwrap())
-            .sum()
+            .keys()
+            .map(|idx| (idx.clone(), self.flip_energy_delta(idx).unwrap()))
+            .collect()
     }
 
-    pub fn magnetization(&self) -> f64 {
+    // `local_energy` attributes every bond in full to both of its endpoints, so the field
+    // sums to twice the exchange energy plus the field energy.
+    pub fn energy_density_field(&self) -> HashMap<LatticePoint, f64> {
         self.spins
+            .keys()
+            .map(|idx| (idx.clone(), self.local_energy(idx).unwrap()))
+            .collect()
+    }
+
+    pub fn energy_along_path(&self, flips: &[LatticePoint]) -> Vec<f64> {
+        let mut path = self.clone();
+        flips
             .iter()
-            .map(|(_, &spin)| match spin {
-                Spin::Up => 1.0,
-                Spin::Down => -1.0,
+            .map(|idx| {
+                let new_spin = path.get_spin(idx).unwrap().flipped();
+                path.set_spin(idx, new_spin).unwrap();
+                path.total_energy()
             })
-            .sum::<f64>()
-            / self.spins.len().value_as::<f64>().unwrap()
+            .collect()
     }
 
-    pub fn metropolis_stepper(&mut self) {
-        let mut rng = rand::thread_rng();
-        let mut idx = Vec::new();
-        for d in 0..self.lattice.dimension {
-            idx.push(rng.gen_range(0..self.lattice.size[d]))
+    pub fn local_energy_variance(&self) -> f64 {
+        let energies: Vec<f64> = self.energy_density_field().into_values().collect();
+        if energies.is_empty() {
+            return 0.0;
         }
-        let init_energy = self.local_energy(idx.as_slice()).unwrap();
-        let mut new_spin = match self.get_spin(idx.as_slice()).unwrap() {
-            Spin::Up => Spin::Down,
-            Spin::Down => Spin::Up,
-        };
-        self.set_spin(idx.as_slice(), new_spin);
-        let energy_change = self.local_energy(idx.as_slice()).unwrap() - init_energy;
-        if energy_change > 0.0
-            && rng.gen::<f64>() > (-energy_change / (BOLTZMANN * self.temperature)).exp()
-        {
-        } else if energy_change < 0.0 {
-        } else {
-            new_spin = match new_spin {
-                Spin::Up => Spin::Down,
-                Spin::Down => Spin::Up,
-            };
-            self.set_spin(idx.as_slice(), new_spin);
+        let sites = energies.len().value_as::<f64>().unwrap();
+        let mean = energies.iter().sum::<f64>() / sites;
+        energies.iter().map(|e| (e - mean).powf(2.0)).sum::<f64>() / sites
+    }
+
+    /// Counts every exchange bond once and every field term once. Since `energy_density_field`
+    /// sees each bond from both ends, the total is half its sum plus half the field energy.
+    pub fn total_energy(&self) -> f64 {
+        if self.field_profile == FieldProfile::None {
+            return 0.5 * self.energy_density_field().values().sum::<f64>();
         }
+        let field_energy: f64 = self
+            .spins
+            .iter()
+            .map(|(idx, spin)| -self.field_at(idx) * spin.value())
+            .sum();
+        0.5 * (self.energy_density_field().values().sum::<f64>() + field_energy)
     }
 
-    pub fn get_up_spin_set(&self) -> OpenSet {
-        self.topology.open_set_from_spins(self, Spin::Up)
+    pub fn energy_per_site(&self) -> f64 {
+        if self.num_sites() == 0 {
+            return 0.0;
+        }
+        self.total_energy() / self.num_sites().value_as::<f64>().unwrap()
     }
 
-    pub fn get_down_spin_set(&self) -> OpenSet {
-        self.topology.open_set_from_spins(self, Spin::Down)
+    pub fn energy_per_bond(&self) -> f64 {
+        let bonds = self.num_bonds();
+        if bonds == 0 {
+            return 0.0;
+        }
+        self.total_energy() / bonds.value_as::<f64>().unwrap()
     }
 
-    pub fn correlation(&self, idx: &[usize]) -> Result<f64, String> {
-        if idx
-            .iter()
-            .zip(&self.lattice.size)
-            .any(|(&i, &cap)| i >= cap)
-        {
-            return Err("Invalid Index".to_string());
+    pub fn exact_density_of_states(&self) -> Result<DensityOfStates, JikiError> {
+        let sites: Vec<LatticePoint> = self.sites().collect();
+        if sites.len() > MAX_ENUMERATION_SITES {
+            return Err(JikiError::TooManySites);
         }
-        let spin = match self.get_spin(idx).unwrap() {
-            Spin::Up => 1.0,
-            Spin::Down => -1.0,
+        let mut configuration = self.clone();
+        let mut energies: Vec<f64> = (0..1u64 << sites.len())
+            .map(|bits| {
+                for (n, idx) in sites.iter().enumerate() {
+                    let spin = if bits >> n & 1 == 1 {
+                        Spin::Up
+                    } else {
+                        Spin::Down
+                    };
+                    configuration.spins.insert(idx.clone(), spin);
+                }
+                configuration.total_energy()
+            })
+            .collect();
+        energies.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        let scale = energies.iter().fold(0.0f64, |m, e| m.max(e.abs()));
+        let mut levels: Vec<(f64, f64)> = Vec::new();
+        for energy in energies {
+            match levels.last_mut() {
+                Some((level, count)) if (energy - *level).abs() <= 1e-9 * scale => *count += 1.0,
+                _ => levels.push((energy, 1.0)),
+            }
+        }
+        Ok(DensityOfStates::new(
+            levels
+                .into_iter()
+                .map(|(energy, count)| (energy, count.ln()))
+                .collect(),
+        ))
+    }
+
+    pub fn energy_gap(&self) -> Result<f64, JikiError> {
+        let levels = self.exact_density_of_states()?.energies;
+        Ok(match levels.as_slice() {
+            [ground, excited, ..] => excited - ground,
+            _ => 0.0,
+        })
+    }
+
+    /// Excess energy over the ordered reference state: the aligned state for ferromagnetic
+    /// coupling, and the lower of the two Néel states for antiferromagnetic coupling, which
+    /// needs a bipartite lattice.
+    pub fn interface_energy(&self) -> Result<f64, JikiError> {
+        let reference = if self.coupling >= 0.0 {
+            let exchange: f64 = self.couplings().iter().map(|(_, _, j)| j).sum();
+            let net_field: f64 = self.spins.keys().map(|idx| self.field_at(idx)).sum();
+            -exchange - net_field.abs()
+        } else {
+            let mut neel = self.clone();
+            neel.neel_state()?;
+            let energy = neel.total_energy();
+            neel.flip_all();
+            energy.min(neel.total_energy())
         };
-        let neighbors = self.nearest_neighbor(idx).unwrap();
-        let neighbor_correlation = neighbors
+        Ok(self.total_energy() - reference)
+    }
+
+    pub fn coarse_grained_magnetization(&self, block: &[usize]) -> HashMap<LatticePoint, f64> {
+        assert!(
+            block.len() == self.lattice.dimension && block.iter().all(|&b| b > 0),
+            "block must be positive and match dimension of lattice"
+        );
+        let mut totals: HashMap<LatticePoint, (f64, f64)> = HashMap::new();
+        for (idx, spin) in &self.spins {
+            let center: LatticePoint = idx
+                .iter()
+                .zip(block)
+                .zip(&self.lattice.size)
+                .map(|((&i, &b), &cap)| {
+                    let origin = i / b * b;
+                    origin + (b.min(cap - origin)) / 2
+                })
+                .collect();
+            let entry = totals.entry(center).or_insert((0.0, 0.0));
+            entry.0 += spin.value();
+            entry.1 += 1.0;
+        }
+        totals
+            .into_iter()
+            .map(|(center, (total, count))| (center, total / count))
+            .collect()
+    }
+
+    pub fn as_lattice_gas_density(&self) -> f64 {
+        (1.0 + self.magnetization()) / 2.0
+    }
+
+    // Occupations n = (1 + s) / 2 with attraction 4J_ij and chemical potential 2H - 2ΣJ_ij,
+    // summed over each site's partners; this differs from `total_energy` by a constant.
+    pub fn lattice_gas_energy(&self) -> f64 {
+        let occupation = |idx: &LatticePoint| (1.0 + self.spins.get(idx).unwrap().value()) / 2.0;
+        let interaction: f64 = self
+            .couplings()
             .iter()
-            .map(|each| match self.get_spin(each.as_slice()).unwrap() {
-                Spin::Up => 1.0 * spin,
-                Spin::Down => -1.0 * spin,
+            .map(|(a, b, coupling)| -4.0 * coupling * occupation(a) * occupation(b))
+            .sum();
+        let chemical: f64 = self
+            .spins
+            .keys()
+            .map(|idx| {
+                let bonded: f64 = self.interacting_sites(idx).iter().map(|(_, j)| j).sum();
+                let potential = 2.0 * self.field_at(idx) - 2.0 * bonded;
+                -potential * occupation(idx)
             })
-            .sum::<f64>()
-            / neighbors.len().value_as::<f64>().unwrap();
-        Ok(neighbor_correlation - self.magnetization().powf(2.0))
+            .sum();
+        interaction + chemical
     }
-}
 
-pub fn abs_distance(a: usize, b: usize) -> usize {
-    if a > b {
-        a - b
-    } else {
-        b - a
+    pub fn is_translation_invariant(&self, shift: &[usize]) -> bool {
+        assert!(
+            shift.len() == self.lattice.dimension,
+            "shift does not match dimension of lattice"
+        );
+        self.lattice.all_points().all(|idx| {
+            let shifted: LatticePoint = idx
+                .iter()
+                .zip(shift)
+                .zip(&self.lattice.size)
+                .map(|((&i, &s), &cap)| (i + s) % cap)
+                .collect();
+            self.spins.get(&idx) == self.spins.get(&shifted)
+        })
+    }
+
+    pub fn reflection_invariant(&self, axis: usize) -> bool {
+        assert!(axis < self.lattice.dimension, "axis out of range");
+        self.lattice.all_points().all(|idx| {
+            let mut reflected = idx.clone();
+            reflected[axis] = self.lattice.size[axis] - 1 - idx[axis];
+            self.spins.get(&idx) == self.spins.get(&reflected)
+        })
+    }
+
+    pub fn region_magnetization(&self, set: &OpenSet) -> f64 {
+        let values: Vec<f64> = set
+            .iter()
+            .filter_map(|idx| self.spins.get(idx))
+            .map(|spin| spin.value())
+            .collect();
+        if values.is_empty() {
+            return 0.0;
+        }
+        values.iter().sum::<f64>() / values.len().value_as::<f64>().unwrap()
+    }
+
+    pub fn flip_all(&mut self) {
+        for spin in self.spins.values_mut() {
+            *spin = spin.flipped();
+        }
+    }
+
+    pub fn symmetrized_magnetization(&self) -> f64 {
+        self.magnetization().abs()
+    }
+
+    pub fn total_magnetization(&self) -> f64 {
+        self.spins.values().map(|spin| spin.value()).sum()
+    }
+
+    /// Zero on a lattice with no occupied sites.
+    pub fn magnetization(&self) -> f64 {
+        if self.num_sites() == 0 {
+            return 0.0;
+        }
+        self.total_magnetization() / self.num_sites().value_as::<f64>().unwrap()
+    }
+
+    pub fn overlap(&self, other: &Ising) -> Result<f64, JikiError> {
+        if self.lattice.size != other.lattice.size {
+            return Err(JikiError::ShapeMismatch);
+        }
+        let products: Vec<f64> = self
+            .spins
+            .iter()
+            .filter_map(|(idx, spin)| Some(spin.value() * other.spins.get(idx)?.value()))
+            .collect();
+        if products.is_empty() {
+            return Ok(0.0);
+        }
+        Ok(products.iter().sum::<f64>() / products.len().value_as::<f64>().unwrap())
+    }
+
+    pub fn hamming_distance(&self, reference: &[Spin]) -> usize {
+        assert!(
+            reference.len() == self.lattice.size.iter().product::<usize>(),
+            "reference does not match number of lattice points"
+        );
+        self.lattice
+            .all_points()
+            .zip(reference)
+            .filter(|(idx, &spin)| matches!(self.spins.get(idx), Some(&s) if s != spin))
+            .count()
+    }
+
+    fn block_patterns(&self, block: &[usize]) -> HashMap<Vec<i8>, f64> {
+        let mut counts: HashMap<Vec<i8>, f64> = HashMap::new();
+        let origins = self
+            .lattice
+            .size
+            .iter()
+            .zip(block)
+            .map(|(&cap, &b)| (0..cap / b).map(move |n| n * b))
+            .multi_cartesian_product();
+        for origin in origins {
+            let pattern = block
+                .iter()
+                .map(|&b| 0..b)
+                .multi_cartesian_product()
+                .map(|offset| {
+                    let idx: LatticePoint =
+                        origin.iter().zip(&offset).map(|(o, d)| o + d).collect();
+                    match self.spins.get(&idx) {
+                        Some(Spin::Up) => 1,
+                        Some(Spin::Down) => -1,
+                        None => 0,
+                    }
+                })
+                .collect();
+            *counts.entry(pattern).or_default() += 1.0;
+        }
+        let total: f64 = counts.values().sum();
+        counts.values_mut().for_each(|count| *count /= total);
+        counts
+    }
+
+    pub fn block_kl_divergence(&self, other: &Ising, block: &[usize]) -> f64 {
+        assert!(
+            self.lattice.size == other.lattice.size,
+            "lattice shapes do not match"
+        );
+        assert!(
+            block.len() == self.lattice.dimension && block.iter().all(|&b| b > 0),
+            "block must be positive and match dimension of lattice"
+        );
+        let p = self.block_patterns(block);
+        let q = other.block_patterns(block);
+        p.iter()
+            .map(|(pattern, &pp)| match q.get(pattern) {
+                Some(&qp) => pp * (pp / qp).ln(),
+                None => f64::INFINITY,
+            })
+            .sum()
+    }
+
+    pub fn overlap_distribution(
+        &mut self,
+        num_replicas: usize,
+        sweeps: usize,
+        bins: usize,
+    ) -> Vec<(f64, usize)> {
+        let replicas: Vec<Ising> = (0..num_replicas)
+            .map(|_| {
+                let mut replica = self.clone();
+                replica.rng = ChaCha12Rng::seed_from_u64(self.rng.gen());
+                replica.randomize();
+                for _ in 0..sweeps {
+                    replica.sweep();
+                }
+                replica
+            })
+            .collect();
+        let width = 2.0 / bins.value_as::<f64>().unwrap();
+        let mut histogram = vec![0; bins];
+        for (i, a) in replicas.iter().enumerate() {
+            for b in &replicas[i + 1..] {
+                let q = a.overlap(b).unwrap();
+                let bin = ((q + 1.0) / width).floor().approx_as::<usize>().unwrap();
+                histogram[bin.min(bins - 1)] += 1;
+            }
+        }
+        histogram
+            .into_iter()
+            .enumerate()
+            .map(|(bin, count)| (-1.0 + (bin.value_as::<f64>().unwrap() + 0.5) * width, count))
+            .collect()
+    }
+
+    pub fn metropolis_stepper(&mut self) {
+        self.step_with(&Metropolis);
+    }
+
+    pub fn step_with(&mut self, updater: &dyn Updater) {
+        self.with_rng(|ising, rng| updater.step(ising, rng));
+    }
+
+    // Moves the model's stream out for the duration of `f`, so that update rules can borrow the
+    // model and the generator at once; the placeholder left behind is never drawn from.
+    fn with_rng<T>(&mut self, f: impl FnOnce(&mut Ising, &mut ChaCha12Rng) -> T) -> T {
+        let mut rng = std::mem::replace(&mut self.rng, ChaCha12Rng::from_seed([0; 32]));
+        let result = f(self, &mut rng);
+        self.rng = rng;
+        result
+    }
+
+    pub fn run(&mut self, updater: &dyn Updater, sweeps: usize) {
+        for _ in 0..sweeps * self.spins.len() {
+            self.step_with(updater);
+        }
+    }
+
+    pub fn flip_rate_classes(&self) -> FlipRateClasses {
+        let mut classes = FlipRateClasses::default();
+        for idx in self.sites() {
+            if !self.pinned.contains(&idx) {
+                let delta = self.flip_energy_delta(&idx).unwrap();
+                classes.insert(idx, delta);
+            }
+        }
+        classes
+    }
+
+    fn flip_rate(&self, energy_change: f64) -> f64 {
+        (-energy_change / (BOLTZMANN * self.temperature))
+            .exp()
+            .min(1.0)
+    }
+
+    // `classes` must come from `flip_rate_classes` on the current configuration. Only the
+    // flipped site and the sites it interacts with are regrouped after each step, so the
+    // classes go stale if the spins are changed by anything other than `bkl_step`.
+    pub fn bkl_step(&mut self, classes: &mut FlipRateClasses) -> f64 {
+        assert!(
+            self.bias.is_none(),
+            "rejection-free steps use local rates and cannot include a bias"
+        );
+        let weighted: Vec<(f64, &[LatticePoint])> = classes
+            .energy_changes()
+            .map(|(delta, sites)| {
+                (
+                    self.flip_rate(delta) * sites.len().value_as::<f64>().unwrap(),
+                    sites,
+                )
+            })
+            .collect();
+        let total_rate: f64 = weighted.iter().map(|(rate, _)| rate).sum();
+        if total_rate <= 0.0 {
+            return f64::INFINITY;
+        }
+        let mut target = self.rng.gen::<f64>() * total_rate;
+        let mut chosen = weighted[weighted.len() - 1].1;
+        for &(class_rate, sites) in &weighted {
+            if target < class_rate {
+                chosen = sites;
+                break;
+            }
+            target -= class_rate;
+        }
+        let idx = chosen[self.rng.gen_range(0..chosen.len())].clone();
+        self.flip_sites(std::slice::from_ref(&idx));
+        self.accepted_flips += 1;
+        let affected = self
+            .interacting_sites(&idx)
+            .into_iter()
+            .map(|(nidx, _)| nidx)
+            .chain(std::iter::once(idx));
+        for site in affected {
+            if self.pinned.contains(&site) {
+                continue;
+            }
+            classes.remove(&site);
+            let delta = self.flip_energy_delta(&site).unwrap();
+            classes.insert(site, delta);
+        }
+        -(1.0 - self.rng.gen::<f64>()).ln() / total_rate
+    }
+
+    pub fn wolff_step(&mut self) -> usize {
+        let sites: Vec<LatticePoint> = self.sites().collect();
+        if sites.is_empty() {
+            return 0;
+        }
+        let seed = sites[self.rng.gen_range(0..sites.len())].clone();
+        if self.pinned.contains(&seed) {
+            return 0;
+        }
+        let mut cluster: HashSet<LatticePoint> = HashSet::from([seed.clone()]);
+        let mut frontier = VecDeque::from([seed]);
+        let mut frozen = false;
+        while let Some(idx) = frontier.pop_front() {
+            let spin = self.get_spin(&idx).unwrap();
+            for (nidx, coupling) in self.interacting_sites(&idx) {
+                if cluster.contains(&nidx) {
+                    continue;
+                }
+                let aligned = self.get_spin(&nidx).unwrap() == spin;
+                let satisfied = (coupling > 0.0) == aligned;
+                if satisfied && self.rng.gen::<f64>() < self.bond_probability(coupling) {
+                    frozen |= self.pinned.contains(&nidx);
+                    cluster.insert(nidx.clone());
+                    frontier.push_back(nidx);
+                }
+            }
+        }
+        if frozen {
+            return 0;
+        }
+        let cluster: Vec<LatticePoint> = cluster.into_iter().collect();
+        let field_change: f64 = cluster
+            .iter()
+            .map(|idx| 2.0 * self.field_at(idx) * self.get_spin(idx).unwrap().value())
+            .sum();
+        let energy_change = field_change + self.bias_change(&cluster);
+        if energy_change > 0.0
+            && self.rng.gen::<f64>() > (-energy_change / (BOLTZMANN * self.temperature)).exp()
+        {
+            return 0;
+        }
+        self.flip_sites(&cluster);
+        self.accepted_flips += cluster.len();
+        cluster.len()
+    }
+
+    pub fn plaquette_step(&mut self, plaquette: &[LatticePoint]) -> bool {
+        if plaquette
+            .iter()
+            .any(|idx| self.pinned.contains(idx) || !self.spins.contains_key(idx))
+        {
+            return false;
+        }
+        let initial_energy = self.total_energy() + self.bias_energy();
+        self.flip_sites(plaquette);
+        let energy_change = self.total_energy() + self.bias_energy() - initial_energy;
+        if energy_change > 0.0
+            && self.rng.gen::<f64>() > (-energy_change / (BOLTZMANN * self.temperature)).exp()
+        {
+            self.flip_sites(plaquette);
+            return false;
+        }
+        self.accepted_flips += plaquette.len();
+        true
+    }
+
+    // Bias energy change if `sites` were flipped; the configuration is left as it was. Without a
+    // bias this is free, so the single-site updates only pay for the O(N) evaluation when umbrella
+    // sampling is on.
+    pub(crate) fn bias_change(&mut self, sites: &[LatticePoint]) -> f64 {
+        if self.bias.is_none() {
+            return 0.0;
+        }
+        let before = self.bias_energy();
+        self.flip_sites(sites);
+        let after = self.bias_energy();
+        self.flip_sites(sites);
+        after - before
+    }
+
+    fn flip_sites(&mut self, sites: &[LatticePoint]) {
+        for idx in sites {
+            let new_spin = self.get_spin(idx).unwrap().flipped();
+            self.set_spin(idx, new_spin).unwrap();
+        }
+    }
+
+    pub fn is_stuck(&mut self, probe_sweeps: usize, threshold: f64) -> bool {
+        let initial = self.spins.clone();
+        let mut changed: HashSet<LatticePoint> = HashSet::new();
+        for _ in 0..probe_sweeps {
+            self.sweep();
+            changed.extend(
+                self.spins
+                    .iter()
+                    .filter(|(idx, spin)| initial.get(*idx) != Some(spin))
+                    .map(|(idx, _)| idx.clone()),
+            );
+        }
+        let fraction =
+            changed.len().value_as::<f64>().unwrap() / self.num_sites().value_as::<f64>().unwrap();
+        fraction < threshold
+    }
+
+    pub fn sequential_sweep(&mut self) -> usize {
+        let sites: Vec<LatticePoint> = self.sites().collect();
+        self.with_rng(|ising, rng| {
+            let mut visited = 0;
+            for idx in &sites {
+                if !ising.pinned.contains(idx) {
+                    metropolis_site(ising, idx, rng);
+                    visited += 1;
+                }
+            }
+            visited
+        })
+    }
+
+    pub fn quench(&mut self, max_sweeps: usize) -> usize {
+        let sites: Vec<LatticePoint> = self.sites().collect();
+        for sweep in 1..=max_sweeps {
+            let mut flipped = false;
+            for idx in &sites {
+                if self.pinned.contains(idx) {
+                    continue;
+                }
+                let energy_change = self.flip_energy_delta(idx).unwrap()
+                    + self.bias_change(std::slice::from_ref(idx));
+                if energy_change >= 0.0 {
+                    continue;
+                }
+                let new_spin = self.get_spin(idx).unwrap().flipped();
+                self.set_spin(idx, new_spin).unwrap();
+                self.accepted_flips += 1;
+                flipped = true;
+            }
+            if !flipped {
+                return sweep;
+            }
+        }
+        max_sweeps
+    }
+
+    pub fn total_accepted_flips(&self) -> usize {
+        self.accepted_flips
+    }
+
+    pub fn reset_accepted_flips(&mut self) {
+        self.accepted_flips = 0;
+    }
+
+    pub fn sweep(&mut self) {
+        for _ in 0..self.spins.len() {
+            self.metropolis_stepper();
+        }
+    }
+
+    // Neal's tempered transition: the excursion from the current temperature up the ladder and
+    // back is accepted with the product of the Boltzmann ratios between adjacent rungs.
+    pub fn tempered_transition(
+        &mut self,
+        temp_ladder: &[f64],
+        sweeps_per_rung: usize,
+    ) -> Result<bool, JikiError> {
+        for &temperature in temp_ladder {
+            check_temperature(temperature)?;
+        }
+        let base_temperature = self.temperature;
+        let betas: Vec<f64> = std::iter::once(base_temperature)
+            .chain(temp_ladder.iter().copied())
+            .map(|t| 1.0 / (BOLTZMANN * t))
+            .collect();
+        let (initial, initial_flips) = (self.spins.clone(), self.accepted_flips);
+        let mut log_acceptance = 0.0;
+        for (i, &temperature) in temp_ladder.iter().enumerate() {
+            log_acceptance +=
+                (betas[i] - betas[i + 1]) * (self.total_energy() + self.bias_energy());
+            self.set_temperature(temperature)?;
+            for _ in 0..sweeps_per_rung {
+                self.sweep();
+            }
+        }
+        for (i, &temperature) in temp_ladder.iter().enumerate().rev() {
+            self.set_temperature(temperature)?;
+            for _ in 0..sweeps_per_rung {
+                self.sweep();
+            }
+            log_acceptance +=
+                (betas[i + 1] - betas[i]) * (self.total_energy() + self.bias_energy());
+        }
+        self.set_temperature(base_temperature)?;
+        let accepted = log_acceptance >= 0.0 || self.rng.gen::<f64>() < log_acceptance.exp();
+        if !accepted {
+            self.spins = initial;
+            self.accepted_flips = initial_flips;
+        }
+        Ok(accepted)
+    }
+
+    pub fn anneal(
+        &mut self,
+        start_temperature: f64,
+        end_temperature: f64,
+        cooling_rate: f64,
+    ) -> Result<(), JikiError> {
+        self.anneal_with_progress(
+            start_temperature,
+            end_temperature,
+            cooling_rate,
+            1,
+            |_, _| {},
+        )
+    }
+
+    pub fn anneal_with_progress(
+        &mut self,
+        start_temperature: f64,
+        end_temperature: f64,
+        cooling_rate: f64,
+        report_interval: usize,
+        mut on_progress: impl FnMut(usize, &Ising),
+    ) -> Result<(), JikiError> {
+        assert!(cooling_rate > 0.0, "cooling rate must be positive");
+        assert!(report_interval > 0, "report interval must be positive");
+        check_temperature(end_temperature)?;
+        self.set_temperature(start_temperature)?;
+        let mut sweeps = 0;
+        loop {
+            self.sweep();
+            sweeps += 1;
+            if sweeps % report_interval == 0 {
+                on_progress(sweeps, self);
+            }
+            if self.temperature <= end_temperature {
+                return Ok(());
+            }
+            let next = (self.temperature - cooling_rate).max(end_temperature);
+            self.set_temperature(next)?;
+        }
+    }
+
+    pub fn run_ac_field(
+        &mut self,
+        amplitude: f64,
+        frequency: f64,
+        sweeps: usize,
+    ) -> Vec<(f64, f64)> {
+        let static_field = self.applied_field;
+        let response = (0..sweeps)
+            .map(|t| {
+                let t = t.value_as::<f64>().unwrap();
+                self.applied_field = amplitude * (2.0 * std::f64::consts::PI * frequency * t).sin();
+                self.sweep();
+                (self.applied_field, self.magnetization())
+            })
+            .collect();
+        self.applied_field = static_field;
+        response
+    }
+
+    // Fortuin-Kasteleyn probability of activating a satisfied bond of strength `coupling`.
+    fn bond_probability(&self, coupling: f64) -> f64 {
+        1.0 - (-2.0 * coupling.abs() / (BOLTZMANN * self.temperature)).exp()
+    }
+
+    pub fn fk_clusters(&self, rng: &mut impl Rng) -> Vec<OpenSet> {
+        let mut visited: HashSet<LatticePoint> = HashSet::new();
+        let mut clusters = Vec::new();
+        for start in self.sites() {
+            if !visited.insert(start.clone()) {
+                continue;
+            }
+            let mut cluster = vec![start.clone()];
+            let mut frontier = VecDeque::from([start]);
+            while let Some(idx) = frontier.pop_front() {
+                let spin = self.get_spin(&idx).unwrap();
+                for (nidx, coupling) in self.interacting_sites(&idx) {
+                    if visited.contains(&nidx) {
+                        continue;
+                    }
+                    let aligned = self.get_spin(&nidx).unwrap() == spin;
+                    let satisfied = (coupling > 0.0) == aligned;
+                    if satisfied && rng.gen::<f64>() < self.bond_probability(coupling) {
+                        visited.insert(nidx.clone());
+                        cluster.push(nidx.clone());
+                        frontier.push_back(nidx);
+                    }
+                }
+            }
+            clusters.push(cluster);
+        }
+        clusters
+    }
+
+    fn mean_site_spins(&mut self, sweeps: usize) -> HashMap<LatticePoint, f64> {
+        let mut totals: HashMap<LatticePoint, f64> = HashMap::new();
+        for _ in 0..sweeps {
+            self.sweep();
+            for (idx, &spin) in &self.spins {
+                *totals.entry(idx.clone()).or_default() += spin.value();
+            }
+        }
+        let samples = sweeps.value_as::<f64>().unwrap();
+        totals
+            .into_iter()
+            .map(|(idx, total)| (idx, total / samples))
+            .collect()
+    }
+
+    pub fn local_susceptibility(
+        &mut self,
+        delta_h: f64,
+        sweeps: usize,
+    ) -> HashMap<LatticePoint, f64> {
+        let base = self.mean_site_spins(sweeps);
+        self.applied_field += delta_h;
+        let perturbed = self.mean_site_spins(sweeps);
+        self.applied_field -= delta_h;
+        base.into_iter()
+            .map(|(idx, mean)| {
+                let response = (perturbed.get(&idx).unwrap() - mean) / delta_h;
+                (idx, response)
+            })
+            .collect()
+    }
+
+    fn mean_magnetization(&mut self, sweeps: usize) -> f64 {
+        let total: f64 = (0..sweeps)
+            .map(|_| {
+                self.sweep();
+                self.magnetization()
+            })
+            .sum();
+        total / sweeps.value_as::<f64>().unwrap()
+    }
+
+    pub fn susceptibility_from_field(&mut self, delta_h: f64, sweeps: usize) -> f64 {
+        let field = self.applied_field;
+        self.applied_field = field + delta_h;
+        let upper = self.mean_magnetization(sweeps);
+        self.applied_field = field - delta_h;
+        let lower = self.mean_magnetization(sweeps);
+        self.applied_field = field;
+        (upper - lower) / (2.0 * delta_h)
+    }
+
+    // A single snapshot's connected correlations sum to zero identically, so the two-point
+    // function is averaged over `sweeps` before the disconnected part is subtracted.
+    pub fn susceptibility_from_correlations(&mut self, sweeps: usize) -> f64 {
+        assert!(sweeps > 0, "sweeps must be positive");
+        let sites: Vec<LatticePoint> = self.sites().collect();
+        let distances: Vec<Vec<usize>> = sites
+            .iter()
+            .map(|a| {
+                sites
+                    .iter()
+                    .map(|b| self.distance(a, b).round().approx_as::<usize>().unwrap())
+                    .collect()
+            })
+            .collect();
+        let shells = distances.iter().flatten().max().map_or(0, |r| r + 1);
+        let mut products = vec![0.0; shells];
+        let mut means = vec![0.0; sites.len()];
+        for _ in 0..sweeps {
+            self.sweep();
+            let values: Vec<f64> = sites
+                .iter()
+                .map(|idx| self.spins.get(idx).unwrap().value())
+                .collect();
+            for (i, si) in values.iter().enumerate() {
+                means[i] += si;
+                for (j, sj) in values.iter().enumerate() {
+                    products[distances[i][j]] += si * sj;
+                }
+            }
+        }
+        let samples = sweeps.value_as::<f64>().unwrap();
+        for m in means.iter_mut() {
+            *m /= samples;
+        }
+        let mut correlation: Vec<f64> = products.into_iter().map(|p| p / samples).collect();
+        for (i, mi) in means.iter().enumerate() {
+            for (j, mj) in means.iter().enumerate() {
+                correlation[distances[i][j]] -= mi * mj;
+            }
+        }
+        let beta = 1.0 / (BOLTZMANN * self.temperature);
+        beta * correlation.iter().sum::<f64>() / sites.len().value_as::<f64>().unwrap()
+    }
+
+    pub fn domains(&self) -> Vec<OpenSet> {
+        let mut visited: HashSet<LatticePoint> = HashSet::new();
+        let mut domains = Vec::new();
+        for start in self.sites() {
+            if !visited.insert(start.clone()) {
+                continue;
+            }
+            let spin = self.get_spin(&start).unwrap();
+            let mut domain = vec![start.clone()];
+            let mut frontier = VecDeque::from([start]);
+            while let Some(idx) = frontier.pop_front() {
+                for nidx in self.nearest_neighbor(&idx).unwrap() {
+                    if self.get_spin(&nidx).unwrap() == spin && visited.insert(nidx.clone()) {
+                        domain.push(nidx.clone());
+                        frontier.push_back(nidx);
+                    }
+                }
+            }
+            domains.push(domain);
+        }
+        domains
+    }
+
+    pub fn percolates(&self, spin: Spin) -> bool {
+        self.domains()
+            .iter()
+            .filter(|domain| self.get_spin(&domain[0]) == Ok(spin))
+            .any(|domain| {
+                (0..self.lattice.dimension).any(|axis| {
+                    let last = self.lattice.size[axis] - 1;
+                    domain.iter().any(|idx| idx[axis] == 0)
+                        && domain.iter().any(|idx| idx[axis] == last)
+                })
+            })
+    }
+
+    pub fn cluster_labels(&self) -> HashMap<LatticePoint, usize> {
+        self.domains()
+            .into_iter()
+            .enumerate()
+            .flat_map(|(label, domain)| domain.into_iter().map(move |idx| (idx, label)))
+            .collect()
+    }
+
+    pub fn pair_connectedness(&self) -> Vec<f64> {
+        let labels = self.cluster_labels();
+        let sites: Vec<LatticePoint> = self.sites().collect();
+        let mut connected: Vec<f64> = Vec::new();
+        let mut pairs: Vec<f64> = Vec::new();
+        for (i, a) in sites.iter().enumerate() {
+            for b in &sites[i..] {
+                let r = self.distance(a, b).round().approx_as::<usize>().unwrap();
+                if r >= pairs.len() {
+                    connected.resize(r + 1, 0.0);
+                    pairs.resize(r + 1, 0.0);
+                }
+                pairs[r] += 1.0;
+                if labels.get(a) == labels.get(b) {
+                    connected[r] += 1.0;
+                }
+            }
+        }
+        connected
+            .into_iter()
+            .zip(pairs)
+            .map(|(c, n)| if n > 0.0 { c / n } else { 0.0 })
+            .collect()
+    }
+
+    pub fn domain_size_distribution(&self) -> BTreeMap<usize, usize> {
+        let mut distribution = BTreeMap::new();
+        for domain in self.domains() {
+            *distribution.entry(domain.len()).or_insert(0) += 1;
+        }
+        distribution
+    }
+
+    pub fn cluster_fractal_dimension(&self) -> f64 {
+        let largest = match self.domains().into_iter().max_by_key(|domain| domain.len()) {
+            Some(domain) => domain,
+            None => return 0.0,
+        };
+        let max_size = self.lattice.size.iter().copied().max().unwrap_or(1);
+        let points: Vec<(f64, f64)> = std::iter::successors(Some(1usize), |s| Some(s * 2))
+            .take_while(|&s| s <= max_size)
+            .map(|s| {
+                let boxes: HashSet<LatticePoint> = largest
+                    .iter()
+                    .map(|idx| idx.iter().map(|&i| i / s).collect())
+                    .collect();
+                let inverse_size = 1.0 / s.value_as::<f64>().unwrap();
+                (
+                    inverse_size.ln(),
+                    boxes.len().value_as::<f64>().unwrap().ln(),
+                )
+            })
+            .collect();
+        if points.len() < 2 {
+            return 0.0;
+        }
+        least_squares_slope(&points)
+    }
+
+    fn two_coloring(&self) -> Option<HashMap<LatticePoint, bool>> {
+        let mut colors: HashMap<LatticePoint, bool> = HashMap::new();
+        for start in self.sites() {
+            if colors.contains_key(&start) {
+                continue;
+            }
+            colors.insert(start.clone(), true);
+            let mut frontier = VecDeque::from([start]);
+            while let Some(idx) = frontier.pop_front() {
+                let color = *colors.get(&idx).unwrap();
+                for nidx in self.nearest_neighbor(&idx).unwrap() {
+                    match colors.get(&nidx) {
+                        Some(&neighbor_color) if neighbor_color == color => return None,
+                        Some(_) => {}
+                        None => {
+                            colors.insert(nidx.clone(), !color);
+                            frontier.push_back(nidx);
+                        }
+                    }
+                }
+            }
+        }
+        Some(colors)
+    }
+
+    pub fn is_bipartite(&self) -> bool {
+        self.two_coloring().is_some()
+    }
+
+    pub fn neel_state(&mut self) -> Result<(), JikiError> {
+        let colors = self.two_coloring().ok_or(JikiError::NotBipartite)?;
+        for (idx, color) in colors {
+            let spin = if color { Spin::Up } else { Spin::Down };
+            self.spins.insert(idx, spin);
+        }
+        Ok(())
+    }
+
+    pub fn thermodynamic_integration(&mut self, field_path: &[f64], sweeps: usize) -> f64 {
+        let static_field = self.applied_field;
+        let sites = self.spins.len().value_as::<f64>().unwrap();
+        let total_magnetizations: Vec<f64> = field_path
+            .iter()
+            .map(|&field| {
+                self.applied_field = field;
+                let mut total = 0.0;
+                for _ in 0..sweeps {
+                    self.sweep();
+                    total += self.magnetization();
+                }
+                total / sweeps.value_as::<f64>().unwrap() * sites
+            })
+            .collect();
+        self.applied_field = static_field;
+        field_path
+            .windows(2)
+            .zip(total_magnetizations.windows(2))
+            .map(|(h, m)| -0.5 * (m[0] + m[1]) * (h[1] - h[0]))
+            .sum()
+    }
+
+    pub fn num_sites(&self) -> usize {
+        self.spins.len()
+    }
+
+    /// Distinct nearest-neighbour pairs. A periodic axis of length two reaches the same site in
+    /// both directions, so a square torus has `2 L^2` bonds only for `L >= 3`; `L = 2` gives 4.
+    pub fn num_bonds(&self) -> usize {
+        self.bonds().len()
+    }
+
+    fn bonds(&self) -> Vec<(Vec<usize>, Vec<usize>)> {
+        self.sites()
+            .flat_map(|idx| {
+                self.nearest_neighbor(&idx)
+                    .unwrap()
+                    .into_iter()
+                    .filter(|nidx| *nidx > idx)
+                    .map(|nidx| (idx.clone(), nidx))
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    // Every interacting pair once, with its coupling; these are `bonds` weighted by `coupling`
+    // for nearest-neighbour interactions.
+    fn couplings(&self) -> Vec<(LatticePoint, LatticePoint, f64)> {
+        self.sites()
+            .flat_map(|idx| {
+                self.interacting_sites(&idx)
+                    .into_iter()
+                    .filter(|(nidx, _)| *nidx > idx)
+                    .map(|(nidx, coupling)| (idx.clone(), nidx, coupling))
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    pub fn alignment_fraction(&self) -> f64 {
+        let bonds = self.bonds();
+        let aligned = bonds
+            .iter()
+            .filter(|(a, b)| self.spins.get(a) == self.spins.get(b))
+            .count();
+        if bonds.is_empty() {
+            return 0.0;
+        }
+        aligned.value_as::<f64>().unwrap() / bonds.len().value_as::<f64>().unwrap()
+    }
+
+    pub fn domain_wall_length(&self) -> usize {
+        self.bonds()
+            .iter()
+            .filter(|(a, b)| self.spins.get(a) != self.spins.get(b))
+            .count()
+    }
+
+    pub fn frustrated_bonds(&self) -> usize {
+        self.couplings()
+            .iter()
+            .filter(|(a, b, coupling)| {
+                let aligned = self.spins.get(a) == self.spins.get(b);
+                (*coupling > 0.0 && !aligned) || (*coupling < 0.0 && aligned)
+            })
+            .count()
+    }
+
+    pub fn kibble_zurek(
+        &mut self,
+        start_temperature: f64,
+        end_temperature: f64,
+        cooling_rates: &[f64],
+    ) -> Result<Vec<(f64, f64)>, JikiError> {
+        check_temperature(start_temperature)?;
+        check_temperature(end_temperature)?;
+        let bond_count = self.couplings().len().value_as::<f64>().unwrap();
+        cooling_rates
+            .iter()
+            .map(|&rate| {
+                self.randomize();
+                self.anneal(start_temperature, end_temperature, rate)?;
+                let defects = self.frustrated_bonds().value_as::<f64>().unwrap();
+                Ok((rate, defects / bond_count))
+            })
+            .collect()
+    }
+
+    pub fn temperature_sweep(
+        &mut self,
+        temperatures: &[f64],
+        burn_in: usize,
+        sweeps: usize,
+        thinning: usize,
+    ) -> Result<Vec<SweepSamples>, JikiError> {
+        self.temperature_sweep_with_progress(temperatures, burn_in, sweeps, thinning, 1, |_, _| {})
+    }
+
+    pub fn temperature_sweep_with_progress(
+        &mut self,
+        temperatures: &[f64],
+        burn_in: usize,
+        sweeps: usize,
+        thinning: usize,
+        report_interval: usize,
+        mut on_progress: impl FnMut(usize, &Ising),
+    ) -> Result<Vec<SweepSamples>, JikiError> {
+        assert!(thinning > 0, "thinning interval must be positive");
+        assert!(report_interval > 0, "report interval must be positive");
+        for &temperature in temperatures {
+            check_temperature(temperature)?;
+        }
+        let mut completed = 0;
+        let mut advance = |ising: &mut Ising| {
+            ising.sweep();
+            completed += 1;
+            if completed % report_interval == 0 {
+                on_progress(completed, ising);
+            }
+        };
+        temperatures
+            .iter()
+            .map(|&temperature| {
+                self.set_temperature(temperature)?;
+                for _ in 0..burn_in {
+                    advance(self);
+                }
+                let mut energies = Vec::new();
+                let mut magnetizations = Vec::new();
+                for s in 1..=sweeps {
+                    advance(self);
+                    if s % thinning == 0 {
+                        energies.push(self.total_energy());
+                        magnetizations.push(self.magnetization());
+                    }
+                }
+                let samples = energies.len().value_as::<f64>().unwrap();
+                Ok(SweepSamples {
+                    temperature,
+                    mean_energy: energies.iter().sum::<f64>() / samples,
+                    mean_magnetization: magnetizations.iter().sum::<f64>() / samples,
+                    energies,
+                    magnetizations,
+                })
+            })
+            .collect()
+    }
+
+    pub fn get_up_spin_set(&self) -> OpenSet {
+        self.topology.open_set_from_spins(self, Spin::Up)
+    }
+
+    pub fn get_down_spin_set(&self) -> OpenSet {
+        self.topology.open_set_from_spins(self, Spin::Down)
+    }
+
+    /// Connected nearest-neighbour correlation: the mean of `s_i * s_j` over the neighbours
+    /// of `idx`, minus the disconnected part `m^2`.
+    pub fn correlation(&self, idx: &[usize]) -> Result<f64, JikiError> {
+        self.correlation_with(idx, self.magnetization())
+    }
+
+    /// `correlation` with a precomputed magnetization, for evaluating many sites of one
+    /// configuration without an O(N) magnetization pass per site.
+    pub fn correlation_with(&self, idx: &[usize], magnetization: f64) -> Result<f64, JikiError> {
+        let spin = self.get_spin(idx)?.value();
+        let neighbors = self.nearest_neighbor(idx)?;
+        if neighbors.is_empty() {
+            return Ok(-magnetization.powf(2.0));
+        }
+        let neighbor_correlation = neighbors
+            .iter()
+            .map(|each| match self.get_spin(each.as_slice()).unwrap() {
+                Spin::Up => spin,
+                Spin::Down => -spin,
+            })
+            .sum::<f64>()
+            / neighbors.len().value_as::<f64>().unwrap();
+        Ok(neighbor_correlation - magnetization.powf(2.0))
+    }
+
+    /// Connected correlation of two regions averaged over `sweeps` sweeps: the mean of
+    /// `s_i * s_j` over `i` in `a` and `j` in `b` is `m_a * m_b` in every configuration, so this
+    /// is `<m_a m_b> - <m_a><m_b>`. Zero if either region has no occupied site.
+    pub fn region_correlation(&mut self, a: &OpenSet, b: &OpenSet, sweeps: usize) -> f64 {
+        assert!(sweeps > 0, "sweeps must be positive");
+        let occupied = |set: &OpenSet| set.iter().any(|idx| self.spins.contains_key(idx));
+        if !occupied(a) || !occupied(b) {
+            return 0.0;
+        }
+        let (mut pairs, mut total_a, mut total_b) = (0.0, 0.0, 0.0);
+        for _ in 0..sweeps {
+            self.sweep();
+            let m_a = self.region_magnetization(a);
+            let m_b = self.region_magnetization(b);
+            pairs += m_a * m_b;
+            total_a += m_a;
+            total_b += m_b;
+        }
+        let samples = sweeps.value_as::<f64>().unwrap();
+        pairs / samples - (total_a / samples) * (total_b / samples)
+    }
+}
+
+pub trait IsingModel {
+    fn local_energy(&self, idx: &[usize]) -> Result<f64, JikiError>;
+    fn total_energy(&self) -> f64;
+    fn net_magnetization(&self) -> f64;
+    fn step(&mut self);
+}
+
+impl IsingModel for Ising {
+    fn local_energy(&self, idx: &[usize]) -> Result<f64, JikiError> {
+        Ising::local_energy(self, idx)
+    }
+
+    fn total_energy(&self) -> f64 {
+        Ising::total_energy(self)
+    }
+
+    fn net_magnetization(&self) -> f64 {
+        self.magnetization()
+    }
+
+    fn step(&mut self) {
+        self.metropolis_stepper();
+    }
+}
+
+/// Rejects the temperatures the Boltzmann factor cannot use: zero, negative and NaN.
+pub(crate) fn check_temperature(temperature: f64) -> Result<(), JikiError> {
+    if temperature.is_nan() || temperature <= 0.0 {
+        return Err(JikiError::InvalidTemperature(temperature));
+    }
+    Ok(())
+}
+
+pub fn abs_distance(a: usize, b: usize) -> usize {
+    a.abs_diff(b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lattice(size: &[usize]) -> Lattice {
+        let mut lattice = Lattice::new(size.len());
+        lattice.set_size(size.to_vec());
+        lattice
+    }
+
+    // The model works in physical units, so tests pass kT and convert here.
+    fn model(size: &[usize], coupling: f64, field: f64, kt: f64, seed: u64) -> Ising {
+        Ising::with_seed(lattice(size), coupling, field, kt / BOLTZMANN, seed)
+    }
+
+    #[test]
+    fn neighbors_match_unit_distance() {
+        use BoundaryCondition::{Open, Periodic};
+        for size in [vec![4, 4], vec![2, 3], vec![1, 4], vec![3, 2, 2]] {
+            let lattice = lattice(&size);
+            for boundary in [Open, Periodic] {
+                let boundary = vec![boundary; size.len()];
+                for metric in [Metric::Manhattan, Metric::Chebyshev, Metric::Euclidean] {
+                    for idx in lattice.all_points() {
+                        let expected: Vec<LatticePoint> = lattice
+                            .all_points()
+                            .filter(|p| lattice.distance(p, &idx, &boundary, metric) == 1.0)
+                            .collect();
+                        assert_eq!(lattice.neighbors(&idx, &boundary, metric), expected);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn nearest_neighbor_follows_boundary_and_metric() {
+        let mut ising = model(&[3, 3], 1.0, 0.0, 1.0, 0);
+        assert_eq!(
+            ising.nearest_neighbor(&[0, 0]).unwrap(),
+            vec![vec![0, 1], vec![1, 0]]
+        );
+        ising.set_boundary(vec![BoundaryCondition::Periodic; 2]);
+        assert_eq!(ising.nearest_neighbor(&[0, 0]).unwrap().len(), 4);
+        ising.metric = Metric::Chebyshev;
+        assert_eq!(ising.nearest_neighbor(&[0, 0]).unwrap().len(), 8);
+        assert!(ising.nearest_neighbor(&[3, 0]).is_err());
+    }
+
+    #[test]
+    fn slower_cooling_leaves_fewer_defects() {
+        let (mut fast, mut slow) = (0.0, 0.0);
+        for seed in 0..6 {
+            let mut ising = model(&[8, 8], 1.0, 0.0, 4.0, seed);
+            ising.set_boundary(vec![BoundaryCondition::Periodic; 2]);
+            let densities = ising
+                .kibble_zurek(
+                    4.0 / BOLTZMANN,
+                    0.5 / BOLTZMANN,
+                    &[1.0 / BOLTZMANN, 0.02 / BOLTZMANN],
+                )
+                .unwrap();
+            fast += densities[0].1;
+            slow += densities[1].1;
+        }
+        assert!(slow < fast);
+    }
+
+    #[test]
+    fn temperature_sweep_keeps_every_kth_sample() {
+        let mut ising = model(&[4, 4], 1.0, 0.0, 2.0, 1);
+        let temperatures = [2.0 / BOLTZMANN, 3.0 / BOLTZMANN];
+        for (thinning, expected) in [(1, 12), (4, 3), (5, 2)] {
+            let samples = ising
+                .temperature_sweep(&temperatures, 3, 12, thinning)
+                .unwrap();
+            assert_eq!(samples.len(), 2);
+            for sample in samples {
+                assert_eq!(sample.energies.len(), expected);
+                assert_eq!(sample.magnetizations.len(), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn metropolis_updater_draws_only_from_the_model_stream() {
+        let mut ising = model(&[4, 4], 1.0, 0.0, 2.5, 11);
+        ising.set_from_profile(|_| 0.0, &mut ChaCha12Rng::seed_from_u64(99));
+        let mut stepped = ising.clone();
+        let mut swept = ising.clone();
+        ising.run(&Metropolis, 3);
+        let mut rng = ChaCha12Rng::seed_from_u64(11);
+        for _ in 0..3 * stepped.num_sites() {
+            Metropolis.step(&mut stepped, &mut rng);
+        }
+        for _ in 0..3 {
+            swept.sweep();
+        }
+        assert!(ising.spins == stepped.spins);
+        assert!(ising.spins == swept.spins);
+    }
+
+    #[test]
+    fn flip_energy_deltas_cover_every_site() {
+        let mut ising = model(&[3, 4], 1.0, 0.3, 1.0, 2);
+        ising.randomize();
+        let deltas = ising.flip_energy_deltas();
+        assert_eq!(deltas.len(), ising.num_sites());
+        for idx in ising.lattice.all_points() {
+            let delta = deltas[&idx];
+            assert_eq!(delta, ising.flip_energy_delta(&idx).unwrap());
+            let mut flipped = ising.clone();
+            flipped.flip_sites(&[idx]);
+            assert!((flipped.total_energy() - ising.total_energy() - delta).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn bkl_steps_evolve_the_magnetization() {
+        let mut ising = model(&[6, 6], 1.0, 0.0, 1.5, 4);
+        ising.randomize();
+        let mut classes = ising.flip_rate_classes();
+        let mut magnetizations = HashSet::new();
+        for _ in 0..200 {
+            let dt = ising.bkl_step(&mut classes);
+            assert!(dt > 0.0 && dt.is_finite());
+            magnetizations.insert(ising.total_magnetization().to_bits());
+        }
+        assert!(magnetizations.len() > 1);
+        assert_eq!(ising.total_accepted_flips(), 200);
+        let rebuilt: Vec<(f64, usize)> = ising
+            .flip_rate_classes()
+            .energy_changes()
+            .map(|(delta, sites)| (delta, sites.len()))
+            .collect();
+        let maintained: Vec<(f64, usize)> = classes
+            .energy_changes()
+            .map(|(delta, sites)| (delta, sites.len()))
+            .collect();
+        assert_eq!(maintained, rebuilt);
+        for (delta, sites) in classes.energy_changes() {
+            for idx in sites {
+                assert_eq!(ising.flip_energy_delta(idx).unwrap(), delta);
+            }
+        }
+    }
+
+    #[test]
+    fn region_correlation_with_itself() {
+        let kt = 3.0;
+        let mut ising = model(&[4, 4], 1.0, 0.0, kt, 5);
+        ising.set_boundary(vec![BoundaryCondition::Periodic; 2]);
+        ising.randomize();
+        let whole: OpenSet = ising.lattice.all_points().collect();
+        let mut replay = ising.clone();
+        let correlation = ising.region_correlation(&whole, &whole, 500);
+        let samples: Vec<f64> = (0..500)
+            .map(|_| {
+                replay.sweep();
+                replay.magnetization()
+            })
+            .collect();
+        let mean = samples.iter().sum::<f64>() / 500.0;
+        let variance = samples.iter().map(|m| (m - mean).powi(2)).sum::<f64>() / 500.0;
+        assert!((correlation - variance).abs() < 1e-12);
+        assert!(correlation > 0.0);
+        let left: OpenSet = vec![vec![0, 0], vec![0, 1]];
+        let right: OpenSet = vec![vec![2, 2], vec![2, 3]];
+        let mut free = model(&[4, 4], 0.0, 0.0, kt, 5);
+        assert!(free.region_correlation(&left, &right, 4000).abs() < 0.02);
+        assert!(free.region_correlation(&left, &left, 4000) > 0.4);
+        assert_eq!(ising.region_correlation(&Vec::new(), &whole, 10), 0.0);
+    }
+
+    #[test]
+    fn ac_response_oscillates_at_the_drive_frequency() {
+        let mut ising = model(&[8, 8], 0.0, 0.0, 1.0, 6);
+        let response = ising.run_ac_field(2.0, 0.05, 200);
+        assert_eq!(response.len(), 200);
+        let power = |frequency: f64| {
+            let (re, im) = response
+                .iter()
+                .enumerate()
+                .fold((0.0, 0.0), |(re, im), (t, (_, m))| {
+                    let phase =
+                        2.0 * std::f64::consts::PI * frequency * t.value_as::<f64>().unwrap();
+                    (re + m * phase.cos(), im + m * phase.sin())
+                });
+            (re * re + im * im).sqrt() / 200.0
+        };
+        assert!(power(0.05) > 0.3);
+        assert!(power(0.05) > 5.0 * power(0.13));
+        assert_eq!(ising.applied_field, 0.0);
+    }
+
+    #[test]
+    fn fk_clusters_merge_an_aligned_lattice_near_zero_temperature() {
+        let ising = model(&[5, 5], 1.0, 0.0, 1e-3, 7);
+        let clusters = ising.fk_clusters(&mut ChaCha12Rng::seed_from_u64(7));
+        assert_eq!(clusters.len(), 1);
+        assert_eq!(clusters[0].len(), 25);
+    }
+
+    #[test]
+    fn local_susceptibility_is_uniform_on_a_torus() {
+        let mut ising = model(&[4, 4], 0.2, 0.0, 2.0, 8);
+        ising.set_boundary(vec![BoundaryCondition::Periodic; 2]);
+        let map = ising.local_susceptibility(0.5, 4000);
+        assert_eq!(map.len(), 16);
+        let mean = map.values().sum::<f64>() / 16.0;
+        assert!(mean > 0.3);
+        assert!(map.values().all(|chi| (chi - mean).abs() < 0.2));
+    }
+
+    #[test]
+    fn chebyshev_neighbors_include_diagonals() {
+        let mut ising = model(&[5, 5], 1.0, 0.0, 1.0, 0);
+        let manhattan = ising.nearest_neighbor(&[2, 2]).unwrap();
+        assert_eq!(
+            manhattan,
+            vec![vec![1, 2], vec![2, 1], vec![2, 3], vec![3, 2]]
+        );
+        ising.metric = Metric::Euclidean;
+        assert_eq!(ising.nearest_neighbor(&[2, 2]).unwrap(), manhattan);
+        assert_eq!(ising.distance(&[0, 0], &[1, 1]), 2f64.sqrt());
+        ising.metric = Metric::Chebyshev;
+        let chebyshev = ising.nearest_neighbor(&[2, 2]).unwrap();
+        assert_eq!(chebyshev.len(), 8);
+        assert!(manhattan.iter().all(|idx| chebyshev.contains(idx)));
+        assert!(chebyshev.contains(&vec![1, 1]) && chebyshev.contains(&vec![3, 3]));
+        assert_eq!(ising.distance(&[0, 0], &[1, 1]), 1.0);
+    }
+
+    #[test]
+    fn energy_density_field_sums_to_twice_the_exchange_energy() {
+        let mut ising = model(&[3, 3], 1.0, 0.0, 1.0, 9);
+        assert_eq!(ising.num_bonds(), 12);
+        assert_eq!(ising.total_energy(), -12.0);
+        ising.applied_field = 0.25;
+        ising.randomize();
+        let density: f64 = ising.energy_density_field().values().sum();
+        let field_energy: f64 = ising
+            .spins
+            .iter()
+            .map(|(idx, spin)| -ising.field_at(idx) * spin.value())
+            .sum();
+        assert!((ising.total_energy() - 0.5 * (density + field_energy)).abs() < 1e-12);
+        let exchange: f64 = ising
+            .bonds()
+            .iter()
+            .map(|(a, b)| -ising.coupling * ising.spins[a].value() * ising.spins[b].value())
+            .sum();
+        assert!((ising.total_energy() - exchange - field_energy).abs() < 1e-12);
+    }
+
+    #[test]
+    fn domain_wall_is_planar_and_halves_the_lattice() {
+        let mut ising = model(&[4, 6], 1.0, 0.0, 1.0, 0);
+        ising.set_domain_wall(0);
+        let up = ising.get_up_spin_set();
+        assert_eq!(up.len(), 12);
+        assert!(up.iter().all(|idx| idx[0] < 2));
+        assert_eq!(ising.domain_wall_length(), 6);
+        ising.set_domain_wall(1);
+        assert!(ising.get_up_spin_set().iter().all(|idx| idx[1] < 3));
+        assert_eq!(ising.domain_wall_length(), 4);
+    }
+
+    #[test]
+    fn overlap_with_self_and_global_flip() {
+        let mut ising = model(&[4, 4], 1.0, 0.0, 1.0, 10);
+        ising.randomize();
+        let mut flipped = ising.clone();
+        flipped.flip_all();
+        assert_eq!(ising.overlap(&ising), Ok(1.0));
+        assert_eq!(ising.overlap(&flipped), Ok(-1.0));
+        let other = model(&[4, 5], 1.0, 0.0, 1.0, 10);
+        assert_eq!(ising.overlap(&other), Err(JikiError::ShapeMismatch));
+    }
+
+    #[test]
+    fn domain_size_distribution_counts_clusters() {
+        let mut ising = model(&[3, 4], 1.0, 0.0, 1.0, 0);
+        ising.reset(Spin::Down);
+        for idx in [[0, 0], [0, 1], [1, 0], [1, 1], [0, 3], [2, 3]] {
+            ising.set_spin(&idx, Spin::Up).unwrap();
+        }
+        let expected = BTreeMap::from([(1, 2), (4, 1), (6, 1)]);
+        assert_eq!(ising.domain_size_distribution(), expected);
+    }
+
+    #[test]
+    fn subregion_copies_the_full_lattice_and_a_corner() {
+        let mut ising = model(&[4, 4], 1.0, 0.0, 1.0, 11);
+        ising.set_boundary(vec![BoundaryCondition::Periodic; 2]);
+        ising.randomize();
+        let whole = ising.subregion(&[0, 0], &[4, 4]);
+        assert!(whole.spins == ising.spins);
+        assert!(whole.boundary == ising.boundary);
+        assert_eq!(whole.total_energy(), ising.total_energy());
+
+        let corner = ising.subregion(&[2, 2], &[2, 2]);
+        assert_eq!(corner.lattice.size, vec![2, 2]);
+        assert!(corner.boundary == vec![BoundaryCondition::Open; 2]);
+        for idx in corner.lattice.all_points() {
+            let global = vec![idx[0] + 2, idx[1] + 2];
+            assert!(corner.get_spin(&idx) == ising.get_spin(&global));
+        }
+    }
+
+    #[test]
+    fn with_magnetization_respects_the_lattice_granularity() {
+        let ising = Ising::with_magnetization(lattice(&[4, 4]), 1.0, 0.0, 1.0, 0.5, 3).unwrap();
+        assert_eq!(ising.get_up_spin_set().len(), 12);
+        assert_eq!(ising.magnetization(), 0.5);
+        for magnetization in [0.3, 1.5] {
+            let result =
+                Ising::with_magnetization(lattice(&[4, 4]), 1.0, 0.0, 1.0, magnetization, 3);
+            assert_eq!(result.err(), Some(JikiError::InvalidMagnetization));
+        }
+    }
+
+    #[test]
+    fn interface_energy_is_twice_the_coupling_per_wall_bond() {
+        let mut ising = model(&[4, 4], 1.5, 0.0, 1.0, 0);
+        assert_eq!(ising.interface_energy().unwrap(), 0.0);
+        ising.set_domain_wall(0);
+        assert_eq!(ising.domain_wall_length(), 4);
+        assert_eq!(ising.interface_energy().unwrap(), 2.0 * 1.5 * 4.0);
+        ising.coupling = 1.0;
+        assert_eq!(ising.interface_energy().unwrap(), 8.0);
+    }
+
+    #[test]
+    fn antiferromagnetic_interface_energy_is_measured_from_the_neel_state() {
+        let mut ising = model(&[4, 4], -1.0, 0.0, 1.0, 0);
+        ising.neel_state().unwrap();
+        assert_eq!(ising.interface_energy().unwrap(), 0.0);
+        ising.flip_all();
+        assert_eq!(ising.interface_energy().unwrap(), 0.0);
+        ising.flip_sites(&[vec![1, 1]]);
+        assert_eq!(ising.interface_energy().unwrap(), 2.0 * 4.0);
+        let mut odd = model(&[3], -1.0, 0.0, 1.0, 0);
+        odd.set_boundary(vec![BoundaryCondition::Periodic]);
+        assert_eq!(odd.interface_energy().err(), Some(JikiError::NotBipartite));
+    }
+
+    fn mean_energy<M: IsingModel>(model: &mut M, steps: usize) -> f64 {
+        let mut total = 0.0;
+        for _ in 0..steps {
+            model.step();
+            total += model.total_energy();
+        }
+        total / steps.value_as::<f64>().unwrap()
+    }
+
+    #[test]
+    fn mean_energy_is_generic_over_models() {
+        use crate::heisenberg::Heisenberg;
+        use crate::xy::XYModel;
+
+        let kt = 0.001;
+        let mut ising = model(&[4, 4], 1.0, 0.0, kt, 1);
+        let mut xy = XYModel::with_seed(lattice(&[4, 4]), 1.0, 0.0, kt / BOLTZMANN, 1);
+        let mut heisenberg =
+            Heisenberg::with_seed(lattice(&[4, 4]), 1.0, [0.0; 3], kt / BOLTZMANN, 1);
+        let ground = -24.0;
+        assert_eq!(mean_energy(&mut ising, 200), ground);
+        assert!((mean_energy(&mut xy, 200) - ground).abs() < 0.1);
+        assert!((mean_energy(&mut heisenberg, 200) - ground).abs() < 0.1);
+        for m in [
+            ising.net_magnetization(),
+            xy.net_magnetization(),
+            heisenberg.net_magnetization(),
+        ] {
+            assert!(m > 0.99);
+        }
+        assert_eq!(
+            IsingModel::local_energy(&xy, &[4, 0]).err(),
+            Some(JikiError::InvalidIndex)
+        );
+        let diluted = Ising::with_dilution(lattice(&[4, 4]), 1.0, 0.0, 1.0, 1.0, 0);
+        assert_eq!(
+            IsingModel::local_energy(&diluted, &[0, 0]).err(),
+            Some(JikiError::VacantSite)
+        );
+    }
+
+    #[test]
+    fn configuration_as_follows_points_in_order() {
+        let mut ising = model(&[2, 3], 1.0, 0.0, 1.0, 0);
+        ising.set_spin(&[0, 1], Spin::Down).unwrap();
+        ising.set_spin(&[1, 2], Spin::Down).unwrap();
+        assert_eq!(ising.configuration_as(1, -1), vec![1, -1, 1, 1, 1, -1]);
+        assert_eq!(ising.configuration_as(1u8, 0u8), vec![1, 0, 1, 1, 1, 0]);
+        let values: Vec<f64> = ising
+            .lattice
+            .points_in_order()
+            .iter()
+            .map(|idx| ising.get_spin(idx).unwrap().value())
+            .collect();
+        assert_eq!(ising.configuration_as(1.0, -1.0), values);
+    }
+
+    #[test]
+    fn neel_state_needs_a_bipartite_lattice() {
+        let mut ising = model(&[4, 4], -1.0, 0.0, 1.0, 0);
+        ising.set_boundary(vec![BoundaryCondition::Periodic; 2]);
+        assert!(ising.is_bipartite());
+        ising.neel_state().unwrap();
+        assert_eq!(ising.magnetization(), 0.0);
+        assert_eq!(ising.frustrated_bonds(), 0);
+        assert_eq!(ising.total_energy(), -32.0);
+
+        let mut odd = model(&[3, 3], -1.0, 0.0, 1.0, 0);
+        assert!(odd.is_bipartite());
+        odd.set_boundary(vec![BoundaryCondition::Periodic; 2]);
+        assert!(!odd.is_bipartite());
+        assert_eq!(odd.neel_state().err(), Some(JikiError::NotBipartite));
+    }
+
+    #[test]
+    fn local_energy_variance_vanishes_on_a_uniform_torus() {
+        let mut ising = model(&[4, 4], 1.0, 0.5, 1.0, 0);
+        ising.set_boundary(vec![BoundaryCondition::Periodic; 2]);
+        assert_eq!(ising.local_energy_variance(), 0.0);
+        ising.set_boundary(vec![BoundaryCondition::Open; 2]);
+        assert!(ising.local_energy_variance() > 0.0);
+    }
+
+    #[test]
+    fn points_in_order_is_row_major() {
+        let expected = vec![
+            vec![0, 0],
+            vec![0, 1],
+            vec![0, 2],
+            vec![1, 0],
+            vec![1, 1],
+            vec![1, 2],
+        ];
+        assert_eq!(lattice(&[2, 3]).points_in_order(), expected);
+        let points: Vec<LatticePoint> = lattice(&[2, 3]).all_points().collect();
+        assert_eq!(points, expected);
+    }
+
+    #[test]
+    fn dilution_extremes_keep_observables_defined() {
+        let full = Ising::with_dilution(lattice(&[4, 4]), 1.0, 0.0, 1.0, 0.0, 5);
+        assert_eq!(full.num_sites(), 16);
+        assert_eq!(full.topology.basis_len(), 18);
+        assert_eq!(full.magnetization(), 1.0);
+
+        let mut empty = Ising::with_dilution(lattice(&[4, 4]), 1.0, 0.5, 1.0, 1.0, 5);
+        empty.sweep();
+        assert_eq!(empty.num_sites(), 0);
+        assert_eq!(empty.topology.basis_len(), 1);
+        assert!(empty.configuration_as(1, -1).is_empty());
+        assert_eq!(empty.configuration_with_vacancies(1, -1, 0), vec![0; 16]);
+        assert_eq!(empty.magnetization(), 0.0);
+        assert_eq!(empty.total_energy(), 0.0);
+        assert_eq!(empty.energy_per_site(), 0.0);
+        assert_eq!(empty.energy_per_bond(), 0.0);
+        assert_eq!(empty.alignment_fraction(), 0.0);
+        assert_eq!(empty.local_energy_variance(), 0.0);
+        assert_eq!(empty.overlap(&empty), Ok(0.0));
+        assert_eq!(empty.local_energy(&[0, 0]), Err(JikiError::VacantSite));
+    }
+
+    #[test]
+    fn cluster_labels_split_two_stripes() {
+        let mut ising = model(&[3, 4], 1.0, 0.0, 1.0, 0);
+        ising.set_domain_wall(1);
+        let labels = ising.cluster_labels();
+        assert_eq!(labels.len(), 12);
+        let left = labels[&vec![0, 0]];
+        let right = labels[&vec![0, 3]];
+        assert_ne!(left, right);
+        for (idx, &label) in &labels {
+            assert_eq!(label, if idx[1] < 2 { left } else { right });
+        }
+    }
+
+    #[test]
+    fn thermodynamic_integration_matches_the_transfer_matrix() {
+        let free_energy = |field: f64| {
+            let (k, h) = (1.0f64, field);
+            let root = ((2.0 * k).exp() * h.sinh().powi(2) + (-2.0 * k).exp()).sqrt();
+            let upper = k.exp() * h.cosh() + root;
+            let lower = k.exp() * h.cosh() - root;
+            -(upper.powi(8) + lower.powi(8)).ln()
+        };
+        let mut ising = model(&[8], 1.0, 0.0, 1.0, 9);
+        ising.set_boundary(vec![BoundaryCondition::Periodic]);
+        for _ in 0..200 {
+            ising.sweep();
+        }
+        let path: Vec<f64> = (0..=10).map(|n| f64::from(n) * 0.1).collect();
+        let estimate = ising.thermodynamic_integration(&path, 4000);
+        let exact = free_energy(1.0) - free_energy(0.0);
+        assert!((estimate - exact).abs() < 0.05 * exact.abs());
+    }
+
+    #[test]
+    fn lattice_gas_density_spans_empty_to_full() {
+        let mut ising = model(&[4, 4], 1.0, 0.3, 1.0, 2);
+        assert_eq!(ising.as_lattice_gas_density(), 1.0);
+        let full = ising.lattice_gas_energy() - ising.total_energy();
+        ising.reset(Spin::Down);
+        assert_eq!(ising.as_lattice_gas_density(), 0.0);
+        assert_eq!(ising.lattice_gas_energy(), 0.0);
+        let empty = ising.lattice_gas_energy() - ising.total_energy();
+        assert!((full - empty).abs() < 1e-9);
+    }
+
+    #[test]
+    fn stripe_percolates_and_blob_does_not() {
+        let mut stripe = model(&[5, 5], 1.0, 0.0, 1.0, 0);
+        stripe.reset(Spin::Down);
+        for j in 0..5 {
+            stripe.set_spin(&[2, j], Spin::Up).unwrap();
+        }
+        assert!(stripe.percolates(Spin::Up));
+
+        let mut blob = model(&[5, 5], 1.0, 0.0, 1.0, 0);
+        blob.reset(Spin::Down);
+        for idx in [[1, 1], [1, 2], [2, 1], [2, 2], [3, 2]] {
+            blob.set_spin(&idx, Spin::Up).unwrap();
+        }
+        assert!(!blob.percolates(Spin::Up));
+        assert!(blob.percolates(Spin::Down));
+    }
+
+    #[test]
+    fn staggered_field_orders_into_a_neel_state() {
+        let mut ising = model(&[4, 4], 0.0, 0.0, 0.05, 4);
+        ising.set_staggered_field(1.0);
+        for _ in 0..50 {
+            ising.sweep();
+        }
+        let mut neel = ising.clone();
+        neel.neel_state().unwrap();
+        let sublattice = neel.get_spin(&[0, 0]).unwrap();
+        if sublattice == Spin::Down {
+            neel.flip_all();
+        }
+        assert!(ising.spins == neel.spins);
+        assert_eq!(ising.magnetization(), 0.0);
+    }
+
+    #[test]
+    fn wolff_orders_an_antiferromagnet() {
+        let mut ising = model(&[6, 6], -1.0, 0.0, 0.5, 8);
+        ising.set_boundary(vec![BoundaryCondition::Periodic; 2]);
+        ising.randomize();
+        for _ in 0..300 {
+            ising.wolff_step();
+        }
+        let staggered: f64 = ising
+            .spins
+            .iter()
+            .map(|(idx, spin)| {
+                let sign = if (idx[0] + idx[1]) % 2 == 0 {
+                    1.0
+                } else {
+                    -1.0
+                };
+                sign * spin.value()
+            })
+            .sum::<f64>()
+            / 36.0;
+        assert!(staggered.abs() > 0.9);
+        assert!(Spin::Up.flipped() == Spin::Down);
+        assert!(Spin::Down.flipped().flipped() == Spin::Down);
+    }
+
+    #[test]
+    fn alignment_fraction_of_uniform_and_checkerboard_states() {
+        let mut ising = model(&[4, 4], 1.0, 0.0, 1.0, 0);
+        assert_eq!(ising.alignment_fraction(), 1.0);
+        ising.neel_state().unwrap();
+        assert_eq!(ising.alignment_fraction(), 0.0);
+    }
+
+    #[test]
+    fn sequential_sweep_visits_each_free_site_once() {
+        let mut ising = model(&[4, 4], 0.0, 0.0, 1.0, 3);
+        ising.randomize();
+        ising.pinned.insert(vec![0, 0]);
+        let before = ising.clone();
+        assert_eq!(ising.sequential_sweep(), 15);
+        assert_eq!(ising.total_accepted_flips(), 15);
+        for (idx, spin) in &before.spins {
+            let expected = if *idx == vec![0, 0] {
+                *spin
+            } else {
+                spin.flipped()
+            };
+            assert!(ising.spins[idx] == expected);
+        }
+    }
+
+    #[test]
+    fn coarse_graining_a_uniform_state_is_uniform() {
+        let mut ising = model(&[4, 6], 1.0, 0.0, 1.0, 0);
+        ising.reset(Spin::Down);
+        let coarse = ising.coarse_grained_magnetization(&[2, 3]);
+        let mut centers: Vec<LatticePoint> = coarse.keys().cloned().collect();
+        centers.sort();
+        assert_eq!(
+            centers,
+            vec![vec![1, 1], vec![1, 4], vec![3, 1], vec![3, 4]]
+        );
+        assert!(coarse.values().all(|&m| m == -1.0));
+    }
+
+    #[test]
+    fn spin_spellings_round_trip() {
+        for spin in [Spin::Up, Spin::Down] {
+            assert!(spin.to_string().parse::<Spin>() == Ok(spin));
+            assert!(format!("{:#}", spin).parse::<Spin>() == Ok(spin));
+        }
+        for text in ["up", " UP ", "+1", "1", "↑"] {
+            assert!(text.parse::<Spin>() == Ok(Spin::Up));
+        }
+        for text in ["down", "Down", "-1", "-", "↓"] {
+            assert!(text.parse::<Spin>() == Ok(Spin::Down));
+        }
+        assert_eq!(
+            "sideways".parse::<Spin>().err(),
+            Some(JikiError::InvalidSpin("sideways".to_string()))
+        );
+    }
+
+    #[test]
+    fn energy_along_path_returns_after_flipping_back() {
+        let mut ising = model(&[4, 4], 1.0, 0.2, 1.0, 12);
+        ising.randomize();
+        let site = vec![1, 2];
+        let start = ising.total_energy();
+        let delta = ising.flip_energy_delta(&site).unwrap();
+        let energies = ising.energy_along_path(&[site.clone(), site]);
+        assert_eq!(energies.len(), 2);
+        assert!((energies[0] - start - delta).abs() < 1e-12);
+        assert!((energies[1] - start).abs() < 1e-12);
+    }
+
+    #[test]
+    fn slab_wraps_in_plane_but_not_across_its_thickness() {
+        use BoundaryCondition::{Open, Periodic};
+        let mut ising = model(&[4, 4, 3], 1.0, 0.0, 1.0, 0);
+        ising.set_boundary(vec![Periodic, Periodic, Open]);
+        let mut neighbors = ising.nearest_neighbor(&[0, 0, 0]).unwrap();
+        neighbors.sort();
+        let expected = vec![
+            vec![0, 0, 1],
+            vec![0, 1, 0],
+            vec![0, 3, 0],
+            vec![1, 0, 0],
+            vec![3, 0, 0],
+        ];
+        assert_eq!(neighbors, expected);
+        assert_eq!(ising.coordination_number(&[2, 2, 1]), 6);
+        assert_eq!(ising.num_bonds(), 2 * 16 * 3 + 16 * 2);
+    }
+
+    #[test]
+    fn density_of_states_matches_direct_energy_fluctuations() {
+        let mut ising = model(&[3, 3], 1.0, 0.3, 1.0, 0);
+        ising.set_boundary(vec![BoundaryCondition::Periodic; 2]);
+        let dos = ising.exact_density_of_states().unwrap();
+        assert_eq!(dos.ln_g.iter().map(|g| g.exp()).sum::<f64>().round(), 512.0);
+        let sites: Vec<LatticePoint> = ising.sites().collect();
+        let energies: Vec<f64> = (0..512u32)
+            .map(|bits| {
+                for (n, idx) in sites.iter().enumerate() {
+                    let spin = if bits >> n & 1 == 1 {
+                        Spin::Up
+                    } else {
+                        Spin::Down
+                    };
+                    ising.set_spin(idx, spin).unwrap();
+                }
+                ising.total_energy()
+            })
+            .collect();
+        for kt in [0.5, 2.27, 5.0] {
+            let beta = 1.0 / kt;
+            let weights: Vec<f64> = energies.iter().map(|e| (-e / kt).exp()).collect();
+            let z: f64 = weights.iter().sum();
+            let moment = |power: i32| {
+                energies
+                    .iter()
+                    .zip(&weights)
+                    .map(|(e, w)| e.powi(power) * w)
+                    .sum::<f64>()
+                    / z
+            };
+            let expected = BOLTZMANN * beta.powi(2) * (moment(2) - moment(1).powi(2));
+            let (temperature, heat) = dos.specific_heat(&[beta])[0];
+            assert!((temperature * BOLTZMANN - kt).abs() < 1e-9);
+            assert!((heat / expected - 1.0).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn overlap_distribution_of_a_cold_ferromagnet_peaks_at_both_signs() {
+        let mut ising = model(&[4, 4], 1.0, 0.0, 1.0, 1);
+        ising.set_boundary(vec![BoundaryCondition::Periodic; 2]);
+        let histogram = ising.overlap_distribution(8, 100, 10);
+        assert_eq!(histogram.len(), 10);
+        let pairs = histogram.iter().map(|(_, count)| count).sum::<usize>();
+        assert_eq!(pairs, 28);
+        let ((low_q, low), (high_q, high)) = (histogram[0], histogram[9]);
+        assert!((low_q + 0.9).abs() < 1e-12 && (high_q - 0.9).abs() < 1e-12);
+        assert!(low > 0 && high > 0);
+        assert!(low + high >= pairs * 4 / 5);
+    }
+
+    #[test]
+    fn non_positive_temperatures_are_rejected() {
+        for temperature in [0.0, -1.0, f64::NAN] {
+            let result = Ising::try_new(lattice(&[2, 2]), 1.0, 0.0, temperature);
+            assert!(matches!(
+                result.err(),
+                Some(JikiError::InvalidTemperature(_))
+            ));
+        }
+        let mut ising = model(&[2, 2], 1.0, 0.0, 1.0, 0);
+        assert_eq!(
+            ising.set_temperature(-1.0),
+            Err(JikiError::InvalidTemperature(-1.0))
+        );
+        assert_eq!(ising.temperature(), 1.0 / BOLTZMANN);
+        assert_eq!(ising.set_temperature(2.0), Ok(()));
+        assert_eq!(ising.temperature(), 2.0);
+    }
+
+    #[test]
+    fn quench_stops_in_a_local_minimum() {
+        let mut ising = model(&[6, 6], 1.0, 0.1, 1.0, 15);
+        ising.randomize();
+        let before = ising.total_energy();
+        let sweeps = ising.quench(100);
+        assert!(sweeps < 100);
+        assert!(ising.total_energy() < before);
+        for idx in ising.lattice.all_points() {
+            assert!(ising.flip_energy_delta(&idx).unwrap() >= 0.0);
+        }
+        assert_eq!(ising.quench(100), 1);
+    }
+
+    #[test]
+    fn steep_power_law_reduces_to_nearest_neighbors() {
+        let mut ising = model(&[4, 4], 1.0, 0.2, 1.0, 16);
+        ising.randomize();
+        let nearest = ising.total_energy();
+        ising.set_long_range(50.0, 3.0);
+        assert!(ising.interacting_sites(&[1, 1]).len() > 4);
+        assert!((ising.total_energy() - nearest).abs() < 1e-9);
+    }
+
+    #[test]
+    fn long_range_stencil_matches_a_scan_of_every_site() {
+        let cases = [
+            (
+                vec![5, 4],
+                BoundaryCondition::Periodic,
+                Metric::Euclidean,
+                2.5,
+            ),
+            (
+                vec![3, 3],
+                BoundaryCondition::Periodic,
+                Metric::Manhattan,
+                3.0,
+            ),
+            (vec![5, 4], BoundaryCondition::Open, Metric::Chebyshev, 2.0),
+            (
+                vec![7],
+                BoundaryCondition::Periodic,
+                Metric::Manhattan,
+                10.0,
+            ),
+        ];
+        for (size, bc, metric, cutoff) in cases {
+            let mut ising = model(&size, 1.0, 0.0, 1.0, 0);
+            ising.metric = metric;
+            ising.set_long_range(1.0, cutoff);
+            ising.set_boundary(vec![bc; size.len()]);
+            ising.spins.remove(&vec![1; size.len()]);
+            for idx in ising.sites() {
+                let mut scan: Vec<(LatticePoint, f64)> = ising
+                    .sites()
+                    .filter_map(|node| {
+                        let r = ising.distance(&node, &idx);
+                        let exponent = ising.lattice.dimension.value_as::<f64>().unwrap() + 1.0;
+                        (r > 0.0 && r <= cutoff).then(|| (node, 1.0 / r.powf(exponent)))
+                    })
+                    .collect();
+                scan.sort_by(|a, b| a.0.cmp(&b.0));
+                assert_eq!(ising.interacting_sites(&idx), scan);
+            }
+        }
+    }
+
+    #[test]
+    fn long_range_wolff_samples_the_boltzmann_distribution() {
+        let kt = 2.0;
+        let mut ising = model(&[8], 1.0, 0.0, kt, 42);
+        ising.set_boundary(vec![BoundaryCondition::Periodic]);
+        ising.set_long_range(0.5, 3.0);
+        let dos = ising.exact_density_of_states().unwrap();
+        let weights: Vec<f64> = dos
+            .energies
+            .iter()
+            .zip(&dos.ln_g)
+            .map(|(energy, ln_g)| (ln_g - energy / kt).exp())
+            .collect();
+        let exact = dos
+            .energies
+            .iter()
+            .zip(&weights)
+            .map(|(energy, weight)| energy * weight)
+            .sum::<f64>()
+            / weights.iter().sum::<f64>();
+        let steps = 5_000;
+        let mut total = 0.0;
+        for _ in 0..steps {
+            ising.wolff_step();
+            total += ising.total_energy();
+        }
+        let sampled = total / steps.value_as::<f64>().unwrap();
+        assert!((sampled - exact).abs() < 0.05 * exact.abs());
+    }
+
+    #[test]
+    fn long_range_bond_observables_use_every_coupling() {
+        let mut ising = model(&[4, 4], 1.0, 0.3, 1.0, 41);
+        ising.set_long_range(1.0, 2.0);
+        assert!(ising.interface_energy().unwrap().abs() < 1e-9);
+        assert_eq!(ising.frustrated_bonds(), 0);
+        let offset = ising.lattice_gas_energy() - ising.total_energy();
+        for _ in 0..5 {
+            ising.randomize();
+            let shifted = ising.lattice_gas_energy() - ising.total_energy();
+            assert!((shifted - offset).abs() < 1e-9);
+        }
+        ising.reset(Spin::Up);
+        ising.set_spin(&[0, 0], Spin::Down).unwrap();
+        assert_eq!(
+            ising.frustrated_bonds(),
+            ising.interacting_sites(&[0, 0]).len()
+        );
+    }
+
+    #[test]
+    fn solid_block_has_fractal_dimension_two() {
+        let ising = model(&[16, 16], 1.0, 0.0, 1.0, 0);
+        assert!((ising.cluster_fractal_dimension() - 2.0).abs() < 1e-12);
+        let line = model(&[16], 1.0, 0.0, 1.0, 0);
+        assert!((line.cluster_fractal_dimension() - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn zero_bias_leaves_every_update_unchanged() {
+        let mut plain = model(&[5, 5], 1.0, 0.3, 2.0, 18);
+        plain.randomize();
+        let mut biased = plain.clone();
+        biased.set_bias(|_| 0.0);
+        assert_eq!(biased.bias_weight(), 1.0);
+        for ising in [&mut plain, &mut biased] {
+            for _ in 0..5 {
+                ising.sweep();
+                ising.wolff_step();
+                ising.plaquette_step(&[vec![0, 0], vec![0, 1], vec![1, 0], vec![1, 1]]);
+            }
+            ising.quench(10);
+        }
+        assert!(biased.spins == plain.spins);
+        assert_eq!(biased.total_accepted_flips(), plain.total_accepted_flips());
+
+        let mut free = model(&[3, 3], 0.0, 0.0, 1.0, 0);
+        free.quench(5);
+        assert_eq!(free.magnetization(), 1.0);
+        free.set_bias(|ising| ising.magnetization());
+        free.quench(5);
+        assert_eq!(free.magnetization(), -1.0);
+    }
+
+    #[test]
+    #[should_panic(expected = "cannot include a bias")]
+    fn bkl_refuses_a_bias() {
+        let mut ising = model(&[3, 3], 1.0, 0.0, 1.0, 0);
+        ising.set_bias(|ising| ising.magnetization());
+        let mut classes = ising.flip_rate_classes();
+        ising.bkl_step(&mut classes);
+    }
+
+    #[test]
+    fn umbrella_samples_concentrate_near_the_center() {
+        let mut ising = model(&[6, 6], 0.0, 0.0, 1.0, 19);
+        ising.randomize();
+        let series = ising.umbrella_run(0.5, 500.0, 300);
+        let tail = &series[50..];
+        let mean = tail.iter().sum::<f64>() / 250.0;
+        assert!((mean - 0.5).abs() < 0.1);
+        assert!(tail.iter().all(|m| (m - 0.5).abs() < 0.35));
+        assert_eq!(ising.bias_energy(), 0.0);
+    }
+
+    #[test]
+    fn hamming_distance_to_self_and_to_the_flipped_state() {
+        let mut ising = model(&[3, 4], 1.0, 0.0, 1.0, 20);
+        ising.randomize();
+        let reference: Vec<Spin> = ising
+            .lattice
+            .all_points()
+            .map(|idx| ising.get_spin(&idx).unwrap())
+            .collect();
+        assert_eq!(ising.hamming_distance(&reference), 0);
+        ising.flip_all();
+        assert_eq!(ising.hamming_distance(&reference), 12);
+    }
+
+    #[test]
+    fn flip_all_reverses_only_the_field_energy() {
+        let mut ising = model(&[4, 4], 1.0, 0.25, 1.0, 21);
+        ising.randomize();
+        let energy = ising.total_energy();
+        let total = ising.total_magnetization();
+        let symmetrized = ising.symmetrized_magnetization();
+        ising.flip_all();
+        assert!((ising.total_energy() - energy - 2.0 * 0.25 * total).abs() < 1e-12);
+        assert_eq!(ising.total_magnetization(), -total);
+        assert_eq!(ising.symmetrized_magnetization(), symmetrized);
+    }
+
+    #[test]
+    fn progress_callbacks_fire_every_report_interval() {
+        let mut ising = model(&[3, 3], 1.0, 0.0, 1.0, 22);
+        let mut calls = Vec::new();
+        ising
+            .anneal_with_progress(5.0, 1.0, 1.0, 2, |sweeps, _| calls.push(sweeps))
+            .unwrap();
+        assert_eq!(calls, vec![2, 4]);
+
+        let mut calls = Vec::new();
+        ising
+            .temperature_sweep_with_progress(&[2.0, 1.0], 3, 4, 1, 5, |sweeps, _| {
+                calls.push(sweeps)
+            })
+            .unwrap();
+        assert_eq!(calls, vec![5, 10]);
+
+        let mut ensemble = crate::ensemble::Ensemble::new(&ising, 3, 0);
+        let mut count = 0;
+        ensemble.mean_observables_with_progress(6, 3, |_, _| count += 1);
+        assert_eq!(count, 6);
+    }
+
+    #[test]
+    fn aligned_lattice_is_connected_at_every_distance() {
+        let mut ising = model(&[4, 4], 1.0, 0.0, 1.0, 0);
+        assert_eq!(ising.pair_connectedness(), vec![1.0; 7]);
+        ising.set_domain_wall(0);
+        let split = ising.pair_connectedness();
+        assert_eq!(split[0], 1.0);
+        assert!(split[1] < 1.0);
+    }
+
+    #[test]
+    fn plaquette_move_pays_for_its_perimeter() {
+        let mut ising = model(&[4, 4], 1.0, 0.0, 0.01, 23);
+        ising.set_boundary(vec![BoundaryCondition::Periodic; 2]);
+        let plaquette = vec![vec![1, 1], vec![1, 2], vec![2, 1], vec![2, 2]];
+        let ground = ising.total_energy();
+        assert!(!ising.plaquette_step(&plaquette));
+        assert_eq!(ising.total_energy(), ground);
+        ising.flip_sites(&plaquette);
+        assert_eq!(ising.total_energy() - ground, 16.0);
+        assert!(ising.plaquette_step(&plaquette));
+        assert_eq!(ising.total_energy(), ground);
+    }
+
+    #[test]
+    fn bond_counts_on_torus_and_open_lattices() {
+        for size in [3, 4, 5] {
+            let mut ising = model(&[size, size], 1.0, 0.0, 1.0, 0);
+            assert_eq!(ising.num_bonds(), 2 * size * (size - 1));
+            ising.set_boundary(vec![BoundaryCondition::Periodic; 2]);
+            assert_eq!(ising.num_bonds(), 2 * size * size);
+            assert_eq!(ising.num_sites(), size * size);
+        }
+        let mut small = model(&[2, 2], 1.0, 0.0, 1.0, 0);
+        small.set_boundary(vec![BoundaryCondition::Periodic; 2]);
+        assert_eq!(small.num_bonds(), 4);
+    }
+
+    #[test]
+    fn aligned_energy_per_bond_is_minus_the_coupling() {
+        let mut ising = model(&[4, 5], 1.5, 0.0, 1.0, 0);
+        assert_eq!(ising.energy_per_bond(), -1.5);
+        ising.set_boundary(vec![BoundaryCondition::Periodic; 2]);
+        assert_eq!(ising.energy_per_bond(), -1.5);
+        assert_eq!(ising.energy_per_site(), -3.0);
+    }
+
+    #[test]
+    fn field_response_matches_magnetization_fluctuations() {
+        let kt = 2.0;
+        let mut ising = model(&[4, 4], 0.0, 0.0, kt, 24);
+        let from_field = ising.susceptibility_from_field(0.2, 4000);
+        let samples: Vec<f64> = (0..4000)
+            .map(|_| {
+                ising.sweep();
+                ising.magnetization()
+            })
+            .collect();
+        let mean = samples.iter().sum::<f64>() / 4000.0;
+        let variance = samples.iter().map(|m| (m - mean).powi(2)).sum::<f64>() / 4000.0;
+        let from_fluctuations = 16.0 * variance / kt;
+        assert!((from_field - 1.0 / kt).abs() < 0.05);
+        assert!((from_fluctuations - 1.0 / kt).abs() < 0.05);
+    }
+
+    #[test]
+    fn frozen_lattice_is_stuck_and_hot_lattice_is_not() {
+        let mut cold = model(&[4, 4], 1.0, 0.0, 0.01, 25);
+        assert!(cold.is_stuck(20, 0.1));
+        let mut hot = model(&[4, 4], 1.0, 0.0, 50.0, 25);
+        assert!(!hot.is_stuck(20, 0.1));
+    }
+
+    #[test]
+    fn domain_wall_length_counts_anti_aligned_bonds() {
+        let mut ising = model(&[4, 6], 1.0, 0.0, 1.0, 0);
+        assert_eq!(ising.domain_wall_length(), 0);
+        ising.set_domain_wall(1);
+        assert_eq!(ising.domain_wall_length(), 4);
+        ising.set_boundary(vec![BoundaryCondition::Periodic; 2]);
+        assert_eq!(ising.domain_wall_length(), 8);
+        ising.neel_state().unwrap();
+        assert_eq!(ising.domain_wall_length(), ising.num_bonds());
+    }
+
+    #[test]
+    fn local_field_is_the_field_plus_coupled_neighbors() {
+        let mut ising = model(&[4, 4], 1.5, 0.5, 1.0, 26);
+        ising.set_boundary(vec![BoundaryCondition::Periodic; 2]);
+        ising.randomize();
+        for idx in ising.lattice.all_points() {
+            let neighbors: f64 = ising
+                .nearest_neighbor(&idx)
+                .unwrap()
+                .iter()
+                .map(|nidx| ising.get_spin(nidx).unwrap().value())
+                .sum();
+            let field = ising.local_field(&idx);
+            assert!((field - (0.5 + 1.5 * neighbors)).abs() < 1e-12);
+            let spin = ising.get_spin(&idx).unwrap().value();
+            assert_eq!(ising.local_energy(&idx).unwrap(), -spin * field);
+        }
+    }
+
+    #[test]
+    fn cluster_plaquette_and_quench_moves_count_their_flips() {
+        let mut ising = model(&[6, 6], 1.0, 0.0, 2.0, 8);
+        ising.randomize();
+        let mut expected = 0;
+        for _ in 0..20 {
+            expected += ising.wolff_step();
+        }
+        assert_eq!(ising.total_accepted_flips(), expected);
+        ising.reset_accepted_flips();
+        let plaquette = vec![vec![1, 1], vec![1, 2], vec![2, 1], vec![2, 2]];
+        let accepted = (0..20).filter(|_| ising.plaquette_step(&plaquette)).count();
+        assert_eq!(ising.total_accepted_flips(), 4 * accepted);
+        ising.reset_accepted_flips();
+        ising.randomize();
+        let before = ising.clone();
+        ising.quench(50);
+        let changed = ising
+            .spins
+            .iter()
+            .filter(|(idx, spin)| before.spins[*idx] != **spin)
+            .count();
+        assert!(ising.total_accepted_flips() >= changed && changed > 0);
+        assert_eq!(ising.total_accepted_flips() % 2, changed % 2);
+    }
+
+    #[test]
+    fn flip_all_is_an_exact_symmetry_without_a_field() {
+        let mut ising = model(&[5, 4], 1.0, 0.0, 1.0, 27);
+        ising.set_boundary(vec![BoundaryCondition::Periodic; 2]);
+        ising.field_profile = FieldProfile::None;
+        ising.randomize();
+        let before = ising.clone();
+        ising.flip_all();
+        assert_eq!(ising.total_energy(), before.total_energy());
+        for idx in ising.lattice.all_points() {
+            assert_eq!(ising.local_field(&idx), -before.local_field(&idx));
+            assert_eq!(
+                ising.local_energy(&idx).unwrap(),
+                before.local_energy(&idx).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn set_from_profile_recovers_the_target_magnetization() {
+        let mut ising = model(&[5, 2000], 1.0, 0.0, 1.0, 28);
+        let mut rng = ChaCha12Rng::seed_from_u64(28);
+        let target = |idx: &LatticePoint| -0.8 + 0.4 * idx[0].value_as::<f64>().unwrap();
+        ising.set_from_profile(target, &mut rng);
+        for row in 0..5 {
+            let column: f64 = (0..2000)
+                .map(|col| ising.get_spin(&[row, col]).unwrap().value())
+                .sum::<f64>()
+                / 2000.0;
+            assert!((column - target(&vec![row, 0])).abs() < 0.05);
+        }
+        ising.set_from_profile(|idx| if idx[1] < 1000 { 2.0 } else { -1.0 }, &mut rng);
+        assert!(ising
+            .spins
+            .iter()
+            .all(|(idx, spin)| *spin == if idx[1] < 1000 { Spin::Up } else { Spin::Down }));
+    }
+
+    #[test]
+    fn correlation_susceptibility_matches_magnetization_fluctuations() {
+        let kt = 3.0;
+        let mut ising = model(&[4, 4], 1.0, 0.0, kt, 29);
+        ising.set_boundary(vec![BoundaryCondition::Periodic; 2]);
+        ising.randomize();
+        let mut replay = ising.clone();
+        let from_correlations = ising.susceptibility_from_correlations(500);
+        let samples: Vec<f64> = (0..500)
+            .map(|_| {
+                replay.sweep();
+                replay.magnetization()
+            })
+            .collect();
+        let mean = samples.iter().sum::<f64>() / 500.0;
+        let variance = samples.iter().map(|m| (m - mean).powi(2)).sum::<f64>() / 500.0;
+        let from_fluctuations = 16.0 * variance / kt;
+        assert!((from_correlations - from_fluctuations).abs() < 1e-9);
+        assert!(from_correlations > 0.0);
+    }
+
+    #[test]
+    fn block_kl_divergence_vanishes_against_itself() {
+        let mut ising = model(&[6, 6], 1.0, 0.0, 1.0, 30);
+        ising.randomize();
+        assert_eq!(ising.block_kl_divergence(&ising, &[2, 2]), 0.0);
+        let mut uniform = ising.clone();
+        uniform.spins.values_mut().for_each(|spin| *spin = Spin::Up);
+        assert_eq!(uniform.block_kl_divergence(&uniform, &[2, 2]), 0.0);
+        assert!(ising.block_kl_divergence(&uniform, &[2, 2]).is_infinite());
+        assert!(uniform.block_kl_divergence(&ising, &[2, 2]) > 0.0);
+    }
+
+    #[test]
+    fn reset_aligns_every_spin_and_clears_the_counter() {
+        let mut ising = model(&[4, 4], 1.0, 0.0, 2.0, 31);
+        ising.randomize();
+        ising.sweep();
+        assert!(ising.total_accepted_flips() > 0);
+        ising.reset(Spin::Down);
+        assert_eq!(ising.total_accepted_flips(), 0);
+        assert_eq!(ising.magnetization(), -1.0);
+        ising.reset(Spin::Up);
+        assert_eq!(ising.magnetization(), 1.0);
+    }
+
+    #[test]
+    fn energy_gap_of_an_open_two_by_two_square() {
+        let ising = model(&[2, 2], 1.0, 0.0, 1.0, 32);
+        assert!((ising.energy_gap().unwrap() - 4.0).abs() < 1e-12);
+        let stronger = model(&[2, 2], 2.5, 0.0, 1.0, 32);
+        assert!((stronger.energy_gap().unwrap() - 10.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn cached_magnetization_correlation_matches_uncached() {
+        let mut ising = model(&[5, 5], 1.0, 0.0, 1.0, 33);
+        ising.set_boundary(vec![BoundaryCondition::Periodic, BoundaryCondition::Open]);
+        ising.randomize();
+        let magnetization = ising.magnetization();
+        for idx in ising.lattice.all_points() {
+            assert_eq!(
+                ising.correlation_with(&idx, magnetization).unwrap(),
+                ising.correlation(&idx).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn region_magnetization_of_the_whole_lattice_is_the_magnetization() {
+        let mut ising = model(&[4, 6], 1.0, 0.0, 1.0, 34);
+        ising.randomize();
+        let whole: OpenSet = ising.lattice.all_points().collect();
+        assert!((ising.region_magnetization(&whole) - ising.magnetization()).abs() < 1e-12);
+        assert_eq!(ising.region_magnetization(&Vec::new()), 0.0);
+    }
+
+    #[test]
+    fn total_magnetization_per_site_is_the_magnetization() {
+        let mut ising = model(&[5, 5], 1.0, 0.0, 1.0, 35);
+        ising.randomize();
+        let sites = ising.num_sites().value_as::<f64>().unwrap();
+        assert!((ising.total_magnetization() / sites - ising.magnetization()).abs() < 1e-12);
+        let mut diluted = Ising::with_dilution(lattice(&[5, 5]), 1.0, 0.0, 1.0, 0.3, 35);
+        diluted.randomize();
+        let occupied = diluted.num_sites().value_as::<f64>().unwrap();
+        assert!(occupied < 25.0);
+        assert!((diluted.total_magnetization() / occupied - diluted.magnetization()).abs() < 1e-12);
+    }
+
+    #[test]
+    fn coordination_is_four_on_a_torus_and_two_at_an_open_corner() {
+        let mut ising = model(&[5, 5], 1.0, 0.0, 1.0, 36);
+        assert_eq!(ising.coordination_number(&[0, 0]), 2);
+        assert_eq!(ising.coordination_number(&[0, 2]), 3);
+        assert_eq!(ising.bulk_coordination_number(), 4);
+        ising.set_boundary(vec![BoundaryCondition::Periodic; 2]);
+        assert!(ising
+            .lattice
+            .all_points()
+            .all(|idx| ising.coordination_number(&idx) == 4));
+    }
+
+    #[test]
+    fn tempered_transitions_sample_the_base_boltzmann_distribution() {
+        let kt = 2.0;
+        let mut ising = model(&[2, 2], 1.0, 0.0, kt, 37);
+        let dos = ising.exact_density_of_states().unwrap();
+        let weights: Vec<f64> = dos
+            .energies
+            .iter()
+            .zip(&dos.ln_g)
+            .map(|(energy, ln_g)| (ln_g - energy / kt).exp())
+            .collect();
+        let partition: f64 = weights.iter().sum();
+        let ladder = [3.0 / BOLTZMANN, 5.0 / BOLTZMANN];
+        let mut counts = vec![0.0; weights.len()];
+        let (mut accepted, transitions) = (0, 20_000);
+        for _ in 0..transitions {
+            let (spins, flips) = (ising.spins.clone(), ising.total_accepted_flips());
+            let moved = ising.tempered_transition(&ladder, 1).unwrap();
+            if !moved {
+                assert!(ising.spins == spins);
+                assert_eq!(ising.total_accepted_flips(), flips);
+            }
+            accepted += usize::from(moved);
+            let energy = ising.total_energy();
+            let level = dos
+                .energies
+                .iter()
+                .position(|e| (e - energy).abs() < 1e-9)
+                .unwrap();
+            counts[level] += 1.0;
+        }
+        assert!(accepted > 0 && accepted < transitions);
+        assert_eq!(ising.temperature(), kt / BOLTZMANN);
+        for (count, weight) in counts.iter().zip(&weights) {
+            assert!((count / 20_000.0 - weight / partition).abs() < 0.02);
+        }
+    }
+
+    #[test]
+    fn stripes_are_invariant_under_their_period_but_not_half_of_it() {
+        let mut ising = model(&[8, 4], 1.0, 0.0, 1.0, 38);
+        for (idx, spin) in ising.spins.iter_mut() {
+            *spin = if (idx[0] / 2) % 2 == 0 {
+                Spin::Up
+            } else {
+                Spin::Down
+            };
+        }
+        assert!(ising.is_translation_invariant(&[4, 0]));
+        assert!(ising.is_translation_invariant(&[0, 1]));
+        assert!(!ising.is_translation_invariant(&[2, 0]));
+        assert!(!ising.is_translation_invariant(&[1, 0]));
+        assert!(ising.reflection_invariant(1));
+        assert!(!ising.reflection_invariant(0));
+        ising.set_spin(&[0, 0], Spin::Down).unwrap();
+        assert!(!ising.is_translation_invariant(&[0, 1]));
+        assert!(!ising.reflection_invariant(1));
+    }
+
+    #[test]
+    fn trait_local_energy_reports_typed_errors() {
+        let mut ising = Ising::with_dilution(lattice(&[3, 3]), 1.0, 0.0, 1.0, 0.5, 3);
+        let vacancy = ising
+            .lattice
+            .all_points()
+            .find(|idx| !ising.spins.contains_key(idx))
+            .unwrap();
+        let energy = |model: &Ising, idx: &[usize]| IsingModel::local_energy(model, idx);
+        assert_eq!(energy(&ising, &vacancy), Err(JikiError::VacantSite));
+        assert_eq!(energy(&ising, &[3, 0]), Err(JikiError::InvalidIndex));
+        assert_eq!(ising.correlation(&[0, 3]), Err(JikiError::InvalidIndex));
+        assert_eq!(
+            ising.set_spin(&vacancy, Spin::Up),
+            Err(JikiError::VacantSite)
+        );
+    }
+
+    #[test]
+    fn invalid_schedules_error_before_touching_the_model() {
+        let mut ising = model(&[4, 4], 1.0, 0.0, 2.0, 23);
+        ising.randomize();
+        let spins = ising.spins.clone();
+        let temperature = ising.temperature();
+        assert_eq!(
+            ising.tempered_transition(&[3.0, -1.0], 1),
+            Err(JikiError::InvalidTemperature(-1.0))
+        );
+        assert_eq!(
+            ising.anneal(3.0, 0.0, 1.0),
+            Err(JikiError::InvalidTemperature(0.0))
+        );
+        assert_eq!(
+            ising.kibble_zurek(-2.0, 1.0, &[1.0]),
+            Err(JikiError::InvalidTemperature(-2.0))
+        );
+        assert!(matches!(
+            ising.temperature_sweep(&[2.0, f64::NAN], 1, 1, 1),
+            Err(JikiError::InvalidTemperature(t)) if t.is_nan()
+        ));
+        assert!(ising.spins == spins);
+        assert_eq!(ising.temperature(), temperature);
     }
 }
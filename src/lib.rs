@@ -0,0 +1,10 @@
+pub mod analysis;
+pub mod continuous;
+pub mod dynamics;
+pub mod ensemble;
+pub mod error;
+pub mod heisenberg;
+pub mod io;
+pub mod ising;
+pub mod topology;
+pub mod xy;
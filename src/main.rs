@@ -1,6 +1,3 @@
-mod ising;
-mod topology;
-
 fn main() {
     println!("Hello, world!");
 }
@@ -7,28 +7,47 @@ use std::collections::HashSet;
 pub type LatticePoint = Vec<usize>;
 pub type OpenSet = Vec<LatticePoint>;
 
+#[derive(Clone)]
 pub struct Topology {
     lattice: Lattice,
+    space: OpenSet,
     basis: HashSet<OpenSet>,
 }
 
 impl Topology {
     pub fn new(lattice: Lattice) -> Self {
+        let points = lattice.all_points().collect();
+        Self::from_points(lattice, points)
+    }
+
+    // The space is the set of occupied sites, so on a diluted lattice vacancies belong to
+    // no basis set and no cover.
+    pub fn from_points(lattice: Lattice, points: OpenSet) -> Self {
         let mut basis = HashSet::new();
         basis.insert(Vec::new());
-        basis.insert(
-            (0..lattice.dimension)
-                .map(|d| 0..lattice.size[d])
-                .multi_cartesian_product()
-                .collect::<Vec<Vec<usize>>>(),
-        );
-        let _ = (0..lattice.dimension)
-            .map(|d| 0..lattice.size[d])
-            .multi_cartesian_product()
-            .collect::<Vec<Vec<usize>>>().iter().map(|p| {
-                basis.insert(vec![p.to_vec()])
-            });
-        Topology { lattice, basis }
+        basis.insert(points.clone());
+        for p in &points {
+            basis.insert(vec![p.clone()]);
+        }
+        Topology { lattice, space: points, basis }
+    }
+
+    pub fn basis_sets(&self) -> impl Iterator<Item = &OpenSet> {
+        self.basis.iter()
+    }
+
+    pub fn basis_len(&self) -> usize {
+        self.basis.len()
+    }
+
+    pub fn basis_by_size(&self) -> Vec<&OpenSet> {
+        let mut sets: Vec<&OpenSet> = self.basis.iter().collect();
+        sets.sort_by(|a, b| a.len().cmp(&b.len()).then_with(|| a.cmp(b)));
+        sets
+    }
+
+    pub fn open_sets_containing(&self, point: &LatticePoint) -> Vec<&OpenSet> {
+        self.basis.iter().filter(|oset| oset.contains(point)).collect()
     }
 
     pub fn intersection(&self, mut sets: Vec<OpenSet>) -> OpenSet {
@@ -37,7 +56,7 @@ impl Topology {
         }
         let mut intersection = sets.pop().unwrap();
         for set in sets {
-            intersection = intersection.into_iter().filter(|point| set.contains(point)).collect();
+            intersection.retain(|point| set.contains(point));
         };
         intersection
     }
@@ -53,11 +72,51 @@ impl Topology {
         result.into_iter().collect()
     }
 
+    pub fn is_subset(&self, a: &OpenSet, b: &OpenSet) -> bool {
+        let larger: HashSet<&LatticePoint> = b.iter().collect();
+        a.iter().all(|point| larger.contains(point))
+    }
+
+    pub fn is_superset(&self, a: &OpenSet, b: &OpenSet) -> bool {
+        self.is_subset(b, a)
+    }
+
+    pub fn sliding_window_cover(&self, window: &[usize], stride: &[usize]) -> Vec<OpenSet> {
+        assert!(
+            window.len() == self.lattice.dimension && stride.len() == self.lattice.dimension,
+            "window and stride must match dimension of lattice"
+        );
+        assert!(stride.iter().all(|&s| s > 0), "stride must be positive");
+        let origins_per_axis: Vec<Vec<usize>> = (0..self.lattice.dimension)
+            .map(|d| {
+                let last = self.lattice.size[d].saturating_sub(window[d]);
+                let mut origins: Vec<usize> = (0..=last).step_by(stride[d]).collect();
+                if origins.last() != Some(&last) {
+                    origins.push(last);
+                }
+                origins
+            })
+            .collect();
+        origins_per_axis
+            .into_iter()
+            .multi_cartesian_product()
+            .map(|origin| {
+                self.space
+                    .iter()
+                    .filter(|point| {
+                        point.iter().zip(&origin).zip(window).all(|((&p, &o), &w)| p >= o && p < o + w)
+                    })
+                    .cloned()
+                    .collect()
+            })
+            .collect()
+    }
+
     pub fn open_set_from_spins(&self, ising: &Ising, spin: Spin) -> OpenSet {
-        ising
-            .lattice
-            .all_points()
-            .filter(|point| ising.get_spin(point).unwrap() == spin)
+        self.space
+            .iter()
+            .filter(|point| ising.get_spin(point) == Ok(spin))
+            .cloned()
             .collect()
     }
 }
@@ -65,7 +124,10 @@ impl Topology {
 pub mod sheaf {
     use std::collections::{BTreeMap, HashMap};
 
+    use conv::prelude::*;
+
     use super::*;
+    use crate::error::JikiError;
 
     #[derive(Clone, PartialEq, Eq, Hash)]
     pub enum Observable {
@@ -74,154 +136,416 @@ pub mod sheaf {
         Correlation,
     }
 
+    // Every section list is ordered like this array.
+    pub const OBSERVABLES: [Observable; 3] = [Observable::Energy, Observable::Spin, Observable::Correlation];
+
     impl Observable {
-        pub fn compute(ising: &Ising, idx: &LatticePoint, obs: Observable) -> Result<f64, String> {
-            if idx
-                .iter()
-                .zip(&ising.lattice.size)
-                .any(|(&i, &cap)| i >= cap)
-            {
-                return Err("Invalid Index".to_string());
-            }
-            let result = match obs {
-                Observable::Energy => ising.local_energy(idx.as_slice()).unwrap(),
-                Observable::Spin => match ising.get_spin(idx.as_slice()).unwrap() {
-                    Spin::Up => 1.0,
-                    Spin::Down => -1.0,
-                },
-                Observable::Correlation => ising.correlation(idx.as_slice()).unwrap(),
+        pub fn compute(ising: &Ising, idx: &LatticePoint, obs: Observable) -> Result<f64, JikiError> {
+            let magnetization = match obs {
+                Observable::Correlation => ising.magnetization(),
+                _ => 0.0,
             };
-            Ok(result)
+            Self::compute_with(ising, idx, obs, magnetization)
+        }
+
+        // Sections evaluate every site of one configuration, so the magnetization needed by
+        // the correlation is computed once by the caller instead of once per site.
+        pub fn compute_with(ising: &Ising, idx: &LatticePoint, obs: Observable, magnetization: f64) -> Result<f64, JikiError> {
+            match obs {
+                Observable::Energy => ising.local_energy(idx.as_slice()),
+                Observable::Spin => Ok(ising.get_spin(idx.as_slice())?.value()),
+                Observable::Correlation => ising.correlation_with(idx.as_slice(), magnetization),
+            }
         }
     }
 
-    type Section<'a> = BTreeMap<&'a LatticePoint, f64>;
+    pub type Section<'a> = BTreeMap<&'a LatticePoint, f64>;
+    pub type SectionRow = (usize, Observable, Vec<(LatticePoint, f64)>);
 
     pub struct Sheaf<'a> {
         topology: &'a Topology,
+        ising: Option<&'a Ising>,
+        magnetization: f64,
         sections: HashMap<&'a Observable, HashMap<&'a OpenSet, Section<'a>>>
     }
 
     impl<'a> Sheaf<'a> {
+        // Sections are snapshots of the configuration they were computed from; call `refresh`
+        // after mutating the model.
         pub fn new(topology: &'a Topology, ising: &Ising) -> Self {
+            Sheaf { topology, ising: None, magnetization: ising.magnetization(), sections: Self::basis_sections(topology, ising) }
+        }
+
+        fn basis_sections(topology: &'a Topology, ising: &Ising) -> HashMap<&'a Observable, HashMap<&'a OpenSet, Section<'a>>> {
+            let magnetization = ising.magnetization();
             let mut all_sections = HashMap::new();
-            for obs in &[Observable::Energy, Observable::Spin, Observable::Correlation] {
+            for obs in &OBSERVABLES {
                 let mut obs_sections = HashMap::new();
                 for oset in &topology.basis {
                     let section: Section = oset.iter().map(|point| {
-                        (point, Observable::compute(ising, point, obs.clone()).unwrap())
+                        (point, Observable::compute_with(ising, point, obs.clone(), magnetization).unwrap())
                     }).collect();
                     obs_sections.insert(oset, section);
                 }
                 all_sections.insert(obs, obs_sections);
             }
-            Sheaf { topology , sections: all_sections }
+            all_sections
+        }
+
+        // Recomputes the basis sections from `ising` and drops every derived section. A lazy
+        // sheaf becomes eager, since its borrowed model is no longer the source of truth.
+        pub fn refresh(&mut self, ising: &Ising) {
+            self.ising = None;
+            self.magnetization = ising.magnetization();
+            self.sections = Self::basis_sections(self.topology, ising);
+        }
+
+        // Sections are computed from `ising` and cached the first time an open set is queried.
+        pub fn lazy(topology: &'a Topology, ising: &'a Ising) -> Self {
+            let sections = OBSERVABLES.iter()
+                .map(|obs| (obs, HashMap::new()))
+                .collect();
+            Sheaf { topology, ising: Some(ising), magnetization: ising.magnetization(), sections }
         }
 
         pub fn get_sections(&mut self, open_set:&'a OpenSet) -> Vec<&Section<'a>> {
             let mut secs = Vec::new();
-            for obs in &[Observable::Energy, Observable::Spin, Observable::Correlation] {
+            for obs in &OBSERVABLES {
                 let mut obs_section_over_oset: Section = BTreeMap::new();
-                for point in open_set {
-                    if let Some((_, sections)) = self.sections.get(obs).unwrap().iter().find(|(basis, _)|basis.contains(&point)) {
-                        obs_section_over_oset.insert(&point, sections.get(&point).unwrap().clone());
+                if let Some(ising) = self.ising {
+                    if self.sections.get(obs).unwrap().contains_key(open_set) {
+                        continue;
+                    }
+                    for point in open_set {
+                        if let Ok(value) = Observable::compute_with(ising, point, obs.clone(), self.magnetization) {
+                            obs_section_over_oset.insert(point, value);
+                        }
+                    }
+                } else {
+                    for point in open_set {
+                        if let Some((_, sections)) = self.sections.get(obs).unwrap().iter().find(|(basis, _)|basis.contains(point)) {
+                            obs_section_over_oset.insert(point, *sections.get(point).unwrap());
+                        }
                     }
                 }
-                self.sections.get_mut(obs).unwrap().insert(&open_set, obs_section_over_oset);
+                self.sections.get_mut(obs).unwrap().insert(open_set, obs_section_over_oset);
             }
-            for obs in &[Observable::Energy, Observable::Spin, Observable::Correlation] {
+            for obs in &OBSERVABLES {
                 secs.push(self.sections.get(obs).unwrap().get(open_set).unwrap());
             }
             secs
         }
 
-        pub fn restrict_sections(&mut self, open_set:&'a OpenSet, smaller_set: &'a OpenSet) -> Result<Vec<Section<'a>>, String> {
-            if smaller_set.iter().all(|point| open_set.contains(point)) == false {
-                Err("Target Open Set is not a subset of the provided start set!".to_string())
+        // A `Section` holds one observable over many points, while the stalk is the germ of
+        // every observable at a single point, so it is keyed by observable instead.
+        pub fn stalk(&self, point: &LatticePoint) -> Result<HashMap<Observable, f64>, JikiError> {
+            if let Some(ising) = self.ising {
+                return OBSERVABLES.into_iter()
+                    .map(|obs| Ok((obs.clone(), Observable::compute_with(ising, point, obs, self.magnetization)?)))
+                    .collect();
+            }
+            let smallest = self.topology.open_sets_containing(point).into_iter()
+                .min_by_key(|oset| oset.len())
+                .ok_or(JikiError::InvalidIndex)?;
+            self.sections.iter().map(|(&obs, obs_sections)| {
+                let value = obs_sections.get(smallest).and_then(|section| section.get(point)).ok_or(JikiError::InvalidIndex)?;
+                Ok((obs.clone(), *value))
+            }).collect()
+        }
+
+        pub fn restrict_sections(&mut self, open_set:&'a OpenSet, smaller_set: &'a OpenSet) -> Result<Vec<Section<'a>>, JikiError> {
+            if !self.topology.is_subset(smaller_set, open_set) {
+                Err(JikiError::NotASubset)
             } else {
                 let initial_sections = self.get_sections(open_set);
                 let mut restricted_sections = Vec::<Section<'a>>::new();
                 for sec in initial_sections {
                     let mut restricted_sec = BTreeMap::new();
                     for point in smaller_set {
-                        let val  = sec.iter().find_map(|(&point, obs)| {
-                            if smaller_set.contains(point) {
-                                Some(obs.clone())
-                            } else {
-                                None
-                            }
-                        }).unwrap();
+                        let val = *sec.get(point).ok_or(JikiError::InvalidIndex)?;
                         restricted_sec.insert(point, val);
                     }
                     restricted_sections.push(restricted_sec);
                 }
-                for (obs, section) in [Observable::Energy, Observable::Spin, Observable::Correlation].iter().zip(restricted_sections.clone()) {
+                for (obs, section) in OBSERVABLES.iter().zip(restricted_sections.clone()) {
                     self.sections.get_mut(obs).unwrap().insert(smaller_set, section);
                 }
                 Ok(restricted_sections)
             }
         }
 
-        pub fn glue(&mut self, open_sets: &'a mut Vec<OpenSet>) -> Result<Vec<Section<'a>>, String> {
-            let intersection = self.topology.intersection(open_sets.clone());
-            if intersection.len() == 0 {
-                return Err("Open sets provided do not overlap!".to_string())
+        pub fn glue_pair(&mut self, a: &'a OpenSet, b: &'a OpenSet) -> Result<Vec<Section<'a>>, JikiError> {
+            let overlap = self.topology.intersection(vec![a.clone(), b.clone()]);
+            let a_sections = self.get_sections(a).into_iter().cloned().collect();
+            let b_sections = self.get_sections(b).into_iter().cloned().collect();
+            Self::glue_sections(a_sections, b_sections, &overlap)
+        }
+
+        pub fn glue(&mut self, open_sets: &'a [OpenSet]) -> Result<Vec<Section<'a>>, JikiError> {
+            let (first, rest) = open_sets.split_first().ok_or(JikiError::EmptyCover)?;
+            let mut covered = first.clone();
+            let mut glued = self.get_sections(first).into_iter().cloned().collect();
+            for oset in rest {
+                let overlap = self.topology.intersection(vec![covered.clone(), oset.clone()]);
+                let sections = self.get_sections(oset).into_iter().cloned().collect();
+                glued = Self::glue_sections(glued, sections, &overlap)?;
+                covered = self.topology.union(vec![covered, oset.clone()]);
             }
-            let mut all_sections: Vec<Vec<&Section>> = Vec::new();
-            for oset in open_sets {
-                all_sections.push(self.sections.values().map(|obs_sections| {
-                    obs_sections.get(oset).unwrap()
-                }).collect());
+            Ok(glued)
+        }
+
+        pub fn sections_table(&mut self, cover: &'a [OpenSet]) -> Vec<SectionRow> {
+            let mut table = Vec::new();
+            for (n, oset) in cover.iter().enumerate() {
+                let sections = self.get_sections(oset);
+                for (obs, section) in OBSERVABLES.into_iter().zip(sections) {
+                    let values = section.iter().map(|(&point, &value)| (point.clone(), value)).collect();
+                    table.push((n, obs, values));
+                }
             }
-            let mut all_restricted_sections: Vec<Vec<Section>> = all_sections.iter().map(|sections| {
-                let restricted_secs: Vec<Section> = sections.iter().map(|&sections| {
-                    let rest_sec: Section = sections.iter().filter_map(|(&point, &val)| {
-                        if intersection.contains(point) {
-                            Some((point, val))
-                        } else {
-                            None
-                        }
-                    }).collect();
-                    rest_sec
-                }).collect();
-                restricted_secs
-            }).collect();
-
-            let mut compliment_sections: Vec<Vec<Section>> = all_sections.iter().map(|sections| {
-                let restricted_secs: Vec<Section> = sections.iter().map(|&sections| {
-                    let rest_sec: Section = sections.iter().filter_map(|(&point, &val)| {
-                        if !intersection.contains(point) {
-                            Some((point, val))
-                        } else {
-                            None
-                        }
-                    }).collect();
-                    rest_sec
-                }).collect();
-                restricted_secs
-            }).collect();
-
-            let mut glued_sections: Vec<Section> = Vec::new();
-            let mut comparison = all_restricted_sections.pop().unwrap();
-            for obs in 0..3 {
-                let mut glued_observable: Section = BTreeMap::new(); 
-                let mut can_glue = Vec::new();
-                for point in &intersection {
-                    let comparison_val = comparison[obs].get(point);
-                    can_glue.push(all_restricted_sections.iter().all(|sections| sections[obs].get(point) == comparison_val));
+            table
+        }
+
+        pub fn global_spin_average(&mut self, cover: &'a [OpenSet]) -> Result<f64, JikiError> {
+            let glued = self.glue(cover)?;
+            let spins = OBSERVABLES.iter().zip(&glued)
+                .find_map(|(obs, section)| (*obs == Observable::Spin).then_some(section))
+                .ok_or(JikiError::EmptyCover)?;
+            if spins.is_empty() {
+                return Err(JikiError::EmptyCover)
+            }
+            Ok(spins.values().sum::<f64>() / spins.len().value_as::<f64>().unwrap())
+        }
+
+        fn glue_sections(left: Vec<Section<'a>>, right: Vec<Section<'a>>, overlap: &OpenSet) -> Result<Vec<Section<'a>>, JikiError> {
+            let mut glued_sections = Vec::new();
+            for (mut left_sec, right_sec) in left.into_iter().zip(right) {
+                if overlap.iter().any(|point| left_sec.get(point) != right_sec.get(point)) {
+                    return Err(JikiError::IncompatibleSections)
                 }
-                if can_glue.into_iter().all(|val| val == true) {
-                    glued_observable.append(&mut comparison[obs]);
-                    while let Some(sec) = compliment_sections.pop() {
-                        let mut obs_sec = sec[obs].clone();
-                        glued_observable.append(&mut obs_sec);
-                    }
-                } else {}
-                glued_sections.push(glued_observable);
+                left_sec.extend(right_sec);
+                glued_sections.push(left_sec);
             }
             Ok(glued_sections)
-        }         
+        }
+    }
+
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::sheaf::*;
+    use super::*;
+    use crate::error::JikiError;
+
+    fn ising(size: &[usize]) -> Ising {
+        let mut lattice = Lattice::new(size.len());
+        lattice.set_size(size.to_vec());
+        Ising::with_seed(lattice, 1.0, 0.0, 1.0 / BOLTZMANN, 0)
     }
 
+    #[test]
+    fn glue_pair_joins_overlapping_intervals() {
+        let mut ising = ising(&[6]);
+        ising.set_spin(&[1], Spin::Down).unwrap();
+        let topology = ising.topology.clone();
+        let a: OpenSet = (0..4).map(|i| vec![i]).collect();
+        let b: OpenSet = (2..6).map(|i| vec![i]).collect();
+        let mut sheaf = Sheaf::new(&topology, &ising);
+        let glued = sheaf.glue_pair(&a, &b).unwrap();
+        assert_eq!(glued.len(), 3);
+        for section in &glued {
+            assert_eq!(section.len(), 6);
+        }
+        let spins: Vec<f64> = glued[1].values().copied().collect();
+        assert_eq!(spins, vec![1.0, -1.0, 1.0, 1.0, 1.0, 1.0]);
+    }
 
+    #[test]
+    fn stalk_matches_observables_at_the_point() {
+        let mut ising = ising(&[3, 3]);
+        ising.set_spin(&[1, 1], Spin::Down).unwrap();
+        let topology = ising.topology.clone();
+        let eager = Sheaf::new(&topology, &ising);
+        let lazy = Sheaf::lazy(&topology, &ising);
+        for point in ising.lattice.all_points() {
+            for sheaf in [&eager, &lazy] {
+                let stalk = sheaf.stalk(&point).unwrap();
+                assert_eq!(stalk.len(), 3);
+                for (obs, value) in stalk {
+                    assert_eq!(value, Observable::compute(&ising, &point, obs).unwrap());
+                }
+            }
+        }
+        assert_eq!(eager.stalk(&vec![3, 0]).err(), Some(JikiError::InvalidIndex));
+        assert_eq!(lazy.stalk(&vec![0, 3]).err(), Some(JikiError::InvalidIndex));
+    }
+
+    #[test]
+    fn restrict_sections_keeps_values_at_each_point() {
+        let mut ising = ising(&[4]);
+        ising.set_spin(&[2], Spin::Down).unwrap();
+        let topology = ising.topology.clone();
+        let whole: OpenSet = (0..4).map(|i| vec![i]).collect();
+        let tail: OpenSet = (1..4).map(|i| vec![i]).collect();
+        let outside: OpenSet = vec![vec![0], vec![5]];
+        let mut sheaf = Sheaf::new(&topology, &ising);
+
+        let restricted = sheaf.restrict_sections(&whole, &tail).unwrap();
+        let spins: Vec<f64> = restricted[1].values().copied().collect();
+        assert_eq!(spins, vec![1.0, -1.0, 1.0]);
+        for point in &tail {
+            assert_eq!(restricted[0][point], ising.local_energy(point).unwrap());
+        }
+
+        let same = sheaf.restrict_sections(&whole, &whole).unwrap();
+        let full: Vec<Section> = sheaf.get_sections(&whole).into_iter().cloned().collect();
+        assert!(same == full);
+
+        let result = sheaf.restrict_sections(&tail, &outside);
+        assert_eq!(result.err(), Some(JikiError::NotASubset));
+    }
+
+    #[test]
+    fn diluted_sheaf_skips_vacancies() {
+        let mut lattice = Lattice::new(2);
+        lattice.set_size(vec![4, 4]);
+        let ising = Ising::with_dilution(lattice, 1.0, 0.0, 1.0 / BOLTZMANN, 0.3, 2);
+        let topology = ising.topology.clone();
+        let sites: OpenSet = ising.sites().collect();
+        assert!(sites.len() < 16);
+        assert_eq!(topology.basis_len(), sites.len() + 2);
+        assert!(topology.basis_sets().flatten().all(|point| sites.contains(point)));
+        let cover = topology.sliding_window_cover(&[2, 2], &[2, 2]);
+        assert_eq!(cover.iter().map(|oset| oset.len()).sum::<usize>(), sites.len());
+
+        let whole: OpenSet = ising.lattice.all_points().collect();
+        let mut eager = Sheaf::new(&topology, &ising);
+        let mut lazy = Sheaf::lazy(&topology, &ising);
+        let eager_sections: Vec<Section> = eager.get_sections(&whole).into_iter().cloned().collect();
+        let lazy_sections: Vec<Section> = lazy.get_sections(&whole).into_iter().cloned().collect();
+        assert!(eager_sections == lazy_sections);
+        assert_eq!(eager_sections[1].len(), sites.len());
+        let vacancy = whole.iter().find(|point| !sites.contains(point)).unwrap();
+        assert_eq!(Observable::compute(&ising, vacancy, Observable::Spin).err(), Some(JikiError::VacantSite));
+    }
+
+    #[test]
+    fn basis_holds_empty_set_singletons_and_whole_space() {
+        let ising = ising(&[2, 3]);
+        let topology = &ising.topology;
+        assert_eq!(topology.basis_len(), 8);
+        let whole: OpenSet = ising.lattice.all_points().collect();
+        let sets: Vec<&OpenSet> = topology.basis_sets().collect();
+        assert!(sets.contains(&&Vec::new()));
+        assert!(sets.contains(&&whole));
+        for point in &whole {
+            assert!(sets.contains(&&vec![point.clone()]));
+        }
+    }
+
+    #[test]
+    fn sliding_windows_cover_the_lattice_with_overlaps() {
+        let ising = ising(&[5, 5]);
+        let topology = &ising.topology;
+        let cover = topology.sliding_window_cover(&[3, 3], &[2, 2]);
+        assert_eq!(cover.len(), 4);
+        assert!(cover.iter().all(|oset| oset.len() == 9));
+        let union: HashSet<LatticePoint> = topology.union(cover.clone()).into_iter().collect();
+        assert_eq!(union.len(), 25);
+        let overlap = topology.intersection(vec![cover[0].clone(), cover[1].clone()]);
+        assert_eq!(overlap.len(), 3);
+        let center = topology.intersection(cover.clone());
+        assert_eq!(center, vec![vec![2, 2]]);
+    }
+
+    #[test]
+    fn lazy_sections_equal_eager_sections() {
+        let mut ising = ising(&[4, 4]);
+        ising.randomize();
+        let topology = ising.topology.clone();
+        let cover = topology.sliding_window_cover(&[2, 3], &[1, 2]);
+        let mut eager = Sheaf::new(&topology, &ising);
+        let mut lazy = Sheaf::lazy(&topology, &ising);
+        for oset in topology.basis_sets().chain(&cover) {
+            let eager_sections: Vec<Section> = eager.get_sections(oset).into_iter().cloned().collect();
+            let lazy_sections: Vec<Section> = lazy.get_sections(oset).into_iter().cloned().collect();
+            assert!(eager_sections == lazy_sections);
+        }
+    }
+
+    #[test]
+    fn open_sets_containing_a_point() {
+        let ising = ising(&[2, 3]);
+        let topology = &ising.topology;
+        let point = vec![1, 2];
+        let mut containing = topology.open_sets_containing(&point);
+        containing.sort_by_key(|oset| oset.len());
+        assert_eq!(containing.len(), 2);
+        assert_eq!(containing[0], &vec![point.clone()]);
+        assert_eq!(containing[1].len(), 6);
+        assert!(topology.open_sets_containing(&vec![2, 0]).is_empty());
+    }
+
+    #[test]
+    fn global_spin_average_equals_the_magnetization() {
+        let mut ising = ising(&[4, 4]);
+        ising.randomize();
+        let topology = ising.topology.clone();
+        let cover = topology.sliding_window_cover(&[2, 2], &[2, 2]);
+        let mut sheaf = Sheaf::new(&topology, &ising);
+        let average = sheaf.global_spin_average(&cover).unwrap();
+        assert!((average - ising.magnetization()).abs() < 1e-12);
+        assert_eq!(sheaf.global_spin_average(&[]).err(), Some(JikiError::EmptyCover));
+    }
+
+    #[test]
+    fn refresh_picks_up_a_flipped_spin() {
+        let mut ising = ising(&[3, 3]);
+        let topology = ising.topology.clone();
+        let point = vec![1, 1];
+        let mut sheaf = Sheaf::new(&topology, &ising);
+        assert_eq!(sheaf.stalk(&point).unwrap()[&Observable::Spin], 1.0);
+        ising.set_spin(&point, Spin::Down).unwrap();
+        assert_eq!(sheaf.stalk(&point).unwrap()[&Observable::Spin], 1.0);
+        sheaf.refresh(&ising);
+        let stalk = sheaf.stalk(&point).unwrap();
+        assert_eq!(stalk[&Observable::Spin], -1.0);
+        assert_eq!(stalk[&Observable::Energy], ising.local_energy(&point).unwrap());
+    }
+
+
+    #[test]
+    fn basis_by_size_runs_from_empty_set_to_whole_lattice() {
+        let ising = ising(&[3, 4]);
+        let sets = ising.topology.basis_by_size();
+        assert_eq!(sets.len(), ising.topology.basis_len());
+        assert!(sets[0].is_empty());
+        let whole = sets[sets.len() - 1];
+        assert_eq!(whole.len(), 12);
+        assert!(ising.lattice.all_points().all(|idx| whole.contains(&idx)));
+        assert!(sets.windows(2).all(|pair| pair[0].len() <= pair[1].len()));
+    }
+
+
+    #[test]
+    fn sections_table_has_a_row_per_cover_set_and_observable() {
+        let mut ising = ising(&[4, 4]);
+        ising.randomize();
+        let topology = ising.topology.clone();
+        let cover = topology.sliding_window_cover(&[2, 3], &[2, 1]);
+        let mut sheaf = Sheaf::lazy(&topology, &ising);
+        let table = sheaf.sections_table(&cover);
+        assert_eq!(table.len(), cover.len() * OBSERVABLES.len());
+        for (row, (n, obs, values)) in table.iter().enumerate() {
+            assert_eq!(*n, row / OBSERVABLES.len());
+            assert!(*obs == OBSERVABLES[row % OBSERVABLES.len()]);
+            assert_eq!(values.len(), cover[*n].len());
+            for (point, value) in values {
+                assert!(cover[*n].contains(point));
+                assert_eq!(*value, Observable::compute(&ising, point, obs.clone()).unwrap());
+            }
+        }
+    }
 }
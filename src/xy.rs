@@ -0,0 +1,219 @@
+use std::f64::consts::{PI, TAU};
+
+use conv::prelude::*;
+use rand::Rng;
+
+use crate::continuous::{ContinuousModel, ContinuousSpin};
+use crate::error::JikiError;
+use crate::ising::{BoundaryCondition, IsingModel, BOLTZMANN};
+use crate::topology::LatticePoint;
+
+fn wrap_angle(angle: f64) -> f64 {
+    let wrapped = (angle + PI).rem_euclid(TAU) - PI;
+    if wrapped == -PI {
+        PI
+    } else {
+        wrapped
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct XYSpin(f64);
+
+impl XYSpin {
+    pub fn new(angle: f64) -> Self {
+        XYSpin(wrap_angle(angle))
+    }
+
+    pub fn angle(&self) -> f64 {
+        self.0
+    }
+}
+
+impl ContinuousSpin for XYSpin {
+    type Field = f64;
+
+    const STEP_SIZE: f64 = 1.0;
+
+    fn aligned() -> Self {
+        XYSpin(0.0)
+    }
+
+    fn random(rng: &mut impl Rng) -> Self {
+        XYSpin(rng.gen_range(-PI..PI))
+    }
+
+    fn perturbed(&self, step_size: f64, rng: &mut impl Rng) -> Self {
+        XYSpin::new(self.0 + rng.gen_range(-step_size..=step_size))
+    }
+
+    fn exchange(&self, other: &Self) -> f64 {
+        (self.0 - other.0).cos()
+    }
+
+    fn field_energy(&self, field: &f64) -> f64 {
+        -field * self.0.cos()
+    }
+}
+
+pub type XYModel = ContinuousModel<XYSpin>;
+
+impl ContinuousModel<XYSpin> {
+    pub fn magnetization(&self) -> [f64; 2] {
+        let sites = self.spins.len().value_as::<f64>().unwrap();
+        let (x, y) = self.spins.values().fold((0.0, 0.0), |(x, y), spin| {
+            (x + spin.0.cos(), y + spin.0.sin())
+        });
+        [x / sites, y / sites]
+    }
+
+    pub fn vorticity(&self, plaquette_corner: &LatticePoint) -> i32 {
+        assert!(
+            self.lattice.dimension == 2,
+            "vorticity is defined on two-dimensional lattices"
+        );
+        let crosses_open_edge = (0..2).any(|axis| {
+            plaquette_corner[axis] + 1 >= self.lattice.size[axis]
+                && self.boundary[axis] == BoundaryCondition::Open
+        });
+        if crosses_open_edge {
+            return 0;
+        }
+        let step = |idx: &LatticePoint, axis: usize| -> LatticePoint {
+            let mut next = idx.clone();
+            next[axis] = (next[axis] + 1) % self.lattice.size[axis];
+            next
+        };
+        let a = plaquette_corner.clone();
+        let b = step(&a, 0);
+        let c = step(&b, 1);
+        let d = step(&a, 1);
+        let winding: f64 = [(&a, &b), (&b, &c), (&c, &d), (&d, &a)]
+            .iter()
+            .map(|(from, to)| wrap_angle(self.spins[*to].0 - self.spins[*from].0))
+            .sum();
+        (winding / TAU).round().approx_as::<i32>().unwrap()
+    }
+
+    // Per-configuration estimator of (1/N) [<sum J cos> - beta <(sum J sin)^2>]; both terms are
+    // linear in the samples, so averaging this over a trajectory gives the helicity modulus.
+    pub fn helicity_modulus(&self, axis: usize) -> f64 {
+        assert!(axis < self.lattice.dimension, "axis out of range");
+        let cap = self.lattice.size[axis];
+        let periodic = self.boundary[axis] == BoundaryCondition::Periodic;
+        let (mut stiffness, mut current) = (0.0, 0.0);
+        for (idx, spin) in &self.spins {
+            if idx[axis] + 1 == cap && !periodic {
+                continue;
+            }
+            let mut next = idx.clone();
+            next[axis] = (idx[axis] + 1) % cap;
+            if next == *idx {
+                continue;
+            }
+            let difference = spin.0 - self.spins[&next].0;
+            stiffness += self.coupling * difference.cos();
+            current += self.coupling * difference.sin();
+        }
+        let beta = 1.0 / (BOLTZMANN * self.temperature);
+        let sites = self.spins.len().value_as::<f64>().unwrap();
+        (stiffness - beta * current * current) / sites
+    }
+}
+
+impl IsingModel for XYModel {
+    fn local_energy(&self, idx: &[usize]) -> Result<f64, JikiError> {
+        ContinuousModel::local_energy(self, idx)
+    }
+
+    fn total_energy(&self) -> f64 {
+        ContinuousModel::total_energy(self)
+    }
+
+    fn net_magnetization(&self) -> f64 {
+        let [x, y] = self.magnetization();
+        x.hypot(y)
+    }
+
+    fn step(&mut self) {
+        self.metropolis_stepper();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ising::Lattice;
+
+    fn xy(size: &[usize], kt: f64, seed: u64) -> XYModel {
+        let mut lattice = Lattice::new(size.len());
+        lattice.set_size(size.to_vec());
+        XYModel::with_seed(lattice, 1.0, 0.0, kt / BOLTZMANN, seed)
+    }
+
+    #[test]
+    fn single_vortex_winds_once_around_its_plaquette() {
+        let mut model = xy(&[5, 5], 1.0, 1);
+        for (idx, spin) in model.spins.iter_mut() {
+            let x = idx[0].value_as::<f64>().unwrap() - 1.5;
+            let y = idx[1].value_as::<f64>().unwrap() - 1.5;
+            *spin = XYSpin::new(y.atan2(x));
+        }
+        for corner in model.lattice.all_points() {
+            let expected = if corner == vec![1, 1] { 1 } else { 0 };
+            assert_eq!(model.vorticity(&corner), expected);
+        }
+        for spin in model.spins.values_mut() {
+            *spin = XYSpin::new(-spin.angle());
+        }
+        assert_eq!(model.vorticity(&vec![1, 1]), -1);
+        model.set_boundary(vec![BoundaryCondition::Periodic; 2]);
+        let total: i32 = model
+            .lattice
+            .all_points()
+            .map(|corner| model.vorticity(&corner))
+            .sum();
+        assert_eq!(total, 0);
+    }
+
+    #[test]
+    fn helicity_modulus_drops_from_ordered_through_critical_to_hot() {
+        let mean_helicity = |kt: f64| {
+            let mut model = xy(&[8, 8], kt, 2);
+            model.set_boundary(vec![BoundaryCondition::Periodic; 2]);
+            assert_eq!(model.helicity_modulus(0), 1.0);
+            for _ in 0..100 {
+                model.sweep();
+            }
+            (0..1000)
+                .map(|_| {
+                    model.sweep();
+                    model.helicity_modulus(0)
+                })
+                .sum::<f64>()
+                / 1000.0
+        };
+        let ordered = mean_helicity(0.3);
+        let critical = mean_helicity(0.9);
+        let hot = mean_helicity(2.0);
+        assert!(ordered > 0.8);
+        assert!(critical < ordered && critical > hot);
+        assert!(hot.abs() < 0.2);
+    }
+
+    #[test]
+    fn invalid_temperatures_are_rejected_with_an_error() {
+        let mut lattice = Lattice::new(2);
+        lattice.set_size(vec![3, 3]);
+        assert!(matches!(
+            XYModel::try_new(lattice, 1.0, 0.0, 0.0),
+            Err(JikiError::InvalidTemperature(_))
+        ));
+        let mut model = xy(&[3, 3], 1.0, 2);
+        assert_eq!(
+            model.set_temperature(-1.0),
+            Err(JikiError::InvalidTemperature(-1.0))
+        );
+        assert_eq!(model.temperature(), 1.0 / BOLTZMANN);
+    }
+}